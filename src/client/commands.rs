@@ -0,0 +1,194 @@
+use std::str::FromStr;
+
+use mongodb::bson::oid::ObjectId;
+
+use strum::IntoEnumIterator;
+
+use crate::commons::{parsing_tasks::ParsingTaskStatus, job::JobStatus, social_network::SocialNetworkEnum, entity::EntityThread};
+
+use super::db::{
+    tasks_db::{get_tasks_with_status, requeue_tasks, cancel_job_tasks},
+    jobs_db::{get_jobs, get_job_progress, update_job_status},
+    controls_db::{get_parser_controls, set_parser_control},
+    workers_db::get_workers,
+    communities_db::get_community_history,
+    entities_db::{get_thread, get_entity_history, get_engagement_trajectory}
+};
+use super::parser_v2::{controls::{ParserControl, ParserState, ControlScope}, task_publisher::TaskPublisher};
+use super::settings::SettingsPtr;
+
+const DEFAULT_FAILED_STATUSES: [ParsingTaskStatus; 2] = [ParsingTaskStatus::Failed, ParsingTaskStatus::DeadLetter];
+
+//maintenance commands:
+//"failed [statuses]" prints failed tasks, "requeue [statuses]" queues them again,
+//"jobs" prints jobs with progress, "pause|resume|cancel <job id>" controls a job,
+//"create <network> <seed> <job name>" starts a named job from the seed of settings,
+//"workers" prints workers sharing the task store with their accounts,
+//"community <network> <name>" prints snapshots of a community, e.g. "community reddit r/rust",
+//"thread <post id>" prints stored post with its comment tree, e.g. "thread t3_abc123",
+//"history <entity id>" prints previous revisions of a post or comment,
+//"engagement <post id>" prints engagement snapshots of a post taken on its revisits,
+//"parser status" prints parser controls, "parser running|paused|draining [network <name>|seed <name>]" sets one.
+//returns false when there is no command and parser should be started,
+//error describes invalid command or arguments
+pub async fn run_command(args: &[String], settings: SettingsPtr) -> Result<bool, String> {
+    let (command, args) = match args.split_first() {
+        Some((command, args)) => (command.as_str(), args),
+        None => return Ok(false)
+    };
+    match command {
+        "failed" => {
+            for task in get_tasks_with_status(get_statuses(args)?).await {
+                println!(
+                    "{} {} attempts: {} error: {}",
+                    task.status,
+                    task.get_key(),
+                    task.retry.attempts,
+                    task.retry.last_error.unwrap_or_default()
+                );
+            }
+        },
+        "requeue" => println!("requeued {} tasks", requeue_tasks(get_statuses(args)?).await),
+        "jobs" => {
+            for job in get_jobs().await {
+                let id = job._id.unwrap();
+                println!("{} {} {} {} {:?}", id, job.name, job.social_network, job.status, get_job_progress(id).await);
+            }
+        },
+        "create" => match args {
+            [social_network, seed, name @ ..] if !name.is_empty() => {
+                match TaskPublisher::create_named_job(settings, get_social_network(social_network)?, seed, name.join(" ")).await {
+                    Some(job) => println!("created job {}", job),
+                    None => println!("no seed {} in settings of {}", seed, social_network)
+                }
+            },
+            _ => return Err(String::from("expected: create <network> <seed> <job name>"))
+        },
+        "pause" => print_job_update(update_job_status(get_job_id(args)?, JobStatus::Active, JobStatus::Paused).await),
+        "resume" => print_job_update(update_job_status(get_job_id(args)?, JobStatus::Paused, JobStatus::Active).await),
+        "cancel" => {
+            let job = get_job_id(args)?;
+            let cancelled = update_job_status(job, JobStatus::Active, JobStatus::Cancelled).await
+                || update_job_status(job, JobStatus::Paused, JobStatus::Cancelled).await;
+            print_job_update(cancelled);
+            if cancelled {
+                println!("cancelled {} tasks", cancel_job_tasks(job).await);
+            }
+        },
+        "workers" => {
+            for worker in get_workers().await {
+                let state = if worker.is_alive() { "alive" } else { "stopped" };
+                println!("{} {} since {} heartbeat {} accounts: {:?}", worker._id, state, worker.start_time, worker.heartbeat_time, worker.accounts);
+            }
+        },
+        "community" => match args {
+            [social_network, name] => {
+                for snapshot in get_community_history(name, get_social_network(social_network)?).await {
+                    println!(
+                        "{} subscribers: {:?} active users: {:?}", 
+                        snapshot.snapshot_time, snapshot.subscribers, snapshot.active_users
+                    );
+                }
+            },
+            _ => return Err(String::from("expected: community <network> <name>"))
+        },
+        "thread" => match args {
+            [post_id] => match get_thread(post_id).await {
+                Some(thread) => print_thread(&thread, 0),
+                None => println!("no post {}", post_id)
+            },
+            _ => return Err(String::from("expected: thread <post id>"))
+        },
+        "history" => match args {
+            [entity_id] => {
+                for version in get_entity_history(entity_id).await {
+                    println!(
+                        "{} {:?} replaced by {:?} title: {:?} content: {:?}",
+                        version.replace_time, version.content_status, version.replaced_by_status, version.title, version.content
+                    );
+                }
+            },
+            _ => return Err(String::from("expected: history <entity id>"))
+        },
+        "engagement" => match args {
+            [post_id] => {
+                for snapshot in get_engagement_trajectory(post_id).await {
+                    println!(
+                        "{} rating: {:?} comments: {:?} upvote ratio: {:?}",
+                        snapshot.snapshot_time, snapshot.rating, snapshot.comments_count, snapshot.upvote_ratio
+                    );
+                }
+            },
+            _ => return Err(String::from("expected: engagement <post id>"))
+        },
+        "parser" => match args.split_first() {
+            Some((state, scope)) if state != "status" => {
+                let state = ParserState::from_str(state)
+                    .map_err(|_| format!("unknown parser state {}, expected: running, paused or draining", state))?;
+                let scope = get_control_scope(scope)?;
+                set_parser_control(&ParserControl::new(scope.clone(), state)).await;
+                println!("{:?} is {}", scope, state);
+            },
+            _ => {
+                for control in get_parser_controls().await {
+                    println!("{:?} {} since {}", control.scope, control.state, control.update_time);
+                }
+            }
+        },
+        _ => return Err(format!("unknown command {}, expected one of: failed, requeue, jobs, create, pause, resume, cancel, workers, community, thread, history, engagement, parser", command))
+    }
+    return Ok(true);
+}
+
+fn get_control_scope(args: &[String]) -> Result<ControlScope, String> {
+    return match args {
+        [] => Ok(ControlScope::Global),
+        [kind, name] if kind == "network" => Ok(ControlScope::SocialNetwork(get_social_network(name)?)),
+        [kind, name] if kind == "seed" => Ok(ControlScope::Seed(name.clone())),
+        _ => Err(String::from("expected: network <name> or seed <name>"))
+    };
+}
+
+fn get_social_network(name: &str) -> Result<SocialNetworkEnum, String> {
+    return SocialNetworkEnum::iter()
+        .find(|social_network| social_network.to_string().eq_ignore_ascii_case(name))
+        .ok_or(format!("unknown social network {}", name));
+}
+
+fn get_statuses(args: &[String]) -> Result<Vec<ParsingTaskStatus>, String> {
+    if args.is_empty() {
+        return Ok(DEFAULT_FAILED_STATUSES.to_vec());
+    }
+    return args
+        .iter()
+        .map(|arg| ParsingTaskStatus::from_str(arg).map_err(|_| format!("unknown task status {}", arg)))
+        .collect();
+}
+
+fn get_job_id(args: &[String]) -> Result<ObjectId, String> {
+    return args
+        .first()
+        .and_then(|arg| ObjectId::parse_str(arg).ok())
+        .ok_or(String::from("expected: <job id>"));
+}
+
+fn print_thread(thread: &EntityThread, indent: usize) {
+    let entity = &thread.entity;
+    println!(
+        "{}{} {} rating: {:?} {}",
+        "  ".repeat(indent),
+        entity.id,
+        entity.author_name.as_deref().unwrap_or_default(),
+        entity.rating,
+        entity.title.as_ref().or(entity.content.as_ref()).map(String::as_str).unwrap_or_default()
+    );
+    thread.replies.iter().for_each(|reply| print_thread(reply, indent + 1));
+}
+
+fn print_job_update(updated: bool) {
+    if updated {
+        println!("job updated");
+    } else {
+        println!("job not found or already in this status");
+    }
+}
@@ -25,7 +25,15 @@ pub type ClientSessionPtr = Rc<RefCell<ClientSession>>;
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, Hash, PartialEq, EnumIter, Display)]
 pub enum DATABASE_COLLECTIONS {
     ENTITIES,
-    PARSING_TASKS
+    ENTITY_VERSIONS,
+    ENGAGEMENT,
+    PARSING_TASKS,
+    COMMUNITIES,
+    DISCOVERED_COMMUNITIES,
+    JOBS,
+    PARSER_CONTROLS,
+    WORKERS,
+    LEADERS
 }
 
 pub trait DBCollection {
@@ -87,6 +95,16 @@ Fut: Future<Output = (R, ClientSession)>
 }
 
 
+const DUPLICATE_KEY_ERROR: i32 = 11000;
+
+//unique index rejected the write, document with the same key exists
+pub fn is_duplicate_key_error(error: &error::Error) -> bool {
+    return matches!(
+        *error.kind,
+        ErrorKind::Write(error::WriteFailure::WriteError(ref write_error)) if write_error.code == DUPLICATE_KEY_ERROR
+    );
+}
+
 pub async fn get_collection<T: DBCollection>() -> Collection<T> {
     return MONGO_CLIENT
         .get()
@@ -0,0 +1,74 @@
+use futures::StreamExt;
+use mongodb::{bson::{doc, self}, options::{FindOptions, UpdateOptions}};
+
+use crate::commons::{community::{CommunitySnapshot, DiscoveredCommunity, DiscoveryStatus}, social_network::SocialNetworkEnum};
+
+use super::client::{DATABASE, DATABASE_COLLECTIONS, insert_if_not_empty, get_collection};
+
+pub async fn insert_community_snapshots(snapshots: &Vec<CommunitySnapshot>) {
+    insert_if_not_empty::<CommunitySnapshot>(snapshots, DATABASE::MANSA, DATABASE_COLLECTIONS::COMMUNITIES).await;
+}
+
+pub async fn get_community_history(name: &str, social_network: SocialNetworkEnum) -> Vec<CommunitySnapshot> {
+    let match_query = doc! {
+        "name": name,
+        "social_network": social_network.to_string()
+    };
+    let options = FindOptions::builder()
+        .sort(doc! { "snapshot_time": 1 })
+        .build();
+    return get_collection::<CommunitySnapshot>()
+        .await
+        .find(match_query, options)
+        .await
+        .expect("unable to get community snapshots")
+        .map(|item| item.expect("unable unwrap community snapshot from cursor stream"))
+        .collect()
+        .await;
+}
+
+//returns false when community is already known
+pub async fn register_discovered_community(community: &DiscoveredCommunity) -> bool {
+    let match_query = doc! {
+        "name": community.name.clone(),
+        "social_network": community.social_network.to_string()
+    };
+    let update_query = doc! {
+        "$setOnInsert": bson::to_document(community).unwrap()
+    };
+    let options = UpdateOptions::builder()
+        .upsert(Some(true))
+        .build();
+    return get_collection::<DiscoveredCommunity>()
+        .await
+        .update_one(match_query, update_query, options)
+        .await
+        .expect("unable to register discovered community")
+        .upserted_id
+        .is_some();
+}
+
+//returns false when community was not in the expected status
+pub async fn update_discovered_community_status(
+    name: &str, 
+    social_network: SocialNetworkEnum, 
+    from: DiscoveryStatus, 
+    to: DiscoveryStatus
+) -> bool {
+    let match_query = doc! {
+        "name": name,
+        "social_network": social_network.to_string(),
+        "status": from.to_string()
+    };
+    let update_query = doc! {
+        "$set": {
+            "status": to.to_string()
+        }
+    };
+    return get_collection::<DiscoveredCommunity>()
+        .await
+        .update_one(match_query, update_query, None)
+        .await
+        .expect("unable to update discovered community")
+        .modified_count > 0;
+}
\ No newline at end of file
@@ -0,0 +1,32 @@
+use futures::StreamExt;
+use mongodb::{bson::{doc, self}, options::ReplaceOptions};
+
+use crate::client::parser_v2::controls::ParserControl;
+
+use super::client::get_collection;
+
+pub async fn get_parser_controls() -> Vec<ParserControl> {
+    return get_collection::<ParserControl>()
+        .await
+        .find(None, None)
+        .await
+        .expect("unable to get parser controls")
+        .map(|item| item.expect("unable unwrap parser control from cursor stream"))
+        .collect()
+        .await;
+}
+
+//one control per scope
+pub async fn set_parser_control(control: &ParserControl) {
+    let match_query = doc! {
+        "scope": bson::to_bson(&control.scope).unwrap()
+    };
+    let options = ReplaceOptions::builder()
+        .upsert(Some(true))
+        .build();
+    get_collection::<ParserControl>()
+        .await
+        .replace_one(match_query, control, options)
+        .await
+        .expect("unable to set parser control");
+}
@@ -1,12 +1,26 @@
-use std::thread;
+use std::{thread, collections::HashMap};
 
-use futures::future::join_all;
-use log::info;
-use mongodb::{bson::{doc, self}, options::UpdateOptions};
+use futures::{future::join_all, StreamExt};
+use log::{info, error};
+use mongodb::{bson::{doc, self}, options::{FindOneAndUpdateOptions, ReturnDocument, FindOptions, IndexOptions}, IndexModel};
 
-use crate::commons::entity::Entity;
+use crate::commons::{entity::{Entity, EntityType, EntityThread, EntityVersion}, engagement::EngagementSnapshot};
 
-use super::client::{DATABASE, DATABASE_COLLECTIONS, insert_if_not_empty, ENTITY_COLLECTION};
+use super::client::{DATABASE, DATABASE_COLLECTIONS, insert_if_not_empty, get_collection, ENTITY_COLLECTION};
+
+//comments of a thread are looked up by their root post
+pub async fn create_entities_indexes() {
+    let index = IndexModel::builder()
+        .keys(doc! {"root_id": 1})
+        .options(IndexOptions::builder().name(Some(String::from("root_id"))).build())
+        .build();
+    ENTITY_COLLECTION
+        .get()
+        .await
+        .create_index(index, None)
+        .await
+        .expect("unable to create entities indexes");
+}
 
 pub async fn insert_entities(entities: &Vec<Entity>) {
     insert_if_not_empty::<Entity>(entities, DATABASE::MANSA, DATABASE_COLLECTIONS::ENTITIES).await;
@@ -16,21 +30,179 @@ pub async fn insert_with_replace(entities: Vec<Entity>) {
     info!("2 {:?}", thread::current().id());
     let mut handlers = Vec::new();
     for item in entities.into_iter() {
-        let options = UpdateOptions::builder()
-            .upsert(Some(true))
-            .build();
-        let match_query = doc! {
-            "id" : {
-                "$eq" : item.id.clone()
-            }
-        };
-        let d = bson::to_document(&item).unwrap();
-        let update_query = doc! {
-            "$set": d
-        };
         handlers.push(
-            tokio::spawn(ENTITY_COLLECTION.get().await.update_one(match_query, update_query, options))
+            tokio::spawn(replace_with_history(item))
         );
     }
     join_all(handlers).await;
-}
\ No newline at end of file
+}
+
+//previous revision is moved to ENTITY_VERSIONS when content or its status changes
+async fn replace_with_history(item: Entity) {
+    let options = FindOneAndUpdateOptions::builder()
+        .upsert(Some(true))
+        .return_document(ReturnDocument::Before)
+        .build();
+    let match_query = doc! {
+        "id" : {
+            "$eq" : item.id.clone()
+        }
+    };
+    let d = bson::to_document(&item).unwrap();
+    let update_query = doc! {
+        "$set": d
+    };
+    let previous = ENTITY_COLLECTION
+        .get()
+        .await
+        .find_one_and_update(match_query, update_query, options)
+        .await;
+    match previous {
+        Ok(Some(previous)) if !previous.has_same_content(&item) => {
+            info!("content of entity {} changed", item.id);
+            get_collection::<EntityVersion>()
+                .await
+                .insert_one(EntityVersion::new(previous, &item), None)
+                .await
+                .inspect_err(|e| error!("unable to insert entity version {}: {}", item.id, e))
+                .ok();
+        },
+        Err(e) => error!("unable to replace entity {}: {}", item.id, e),
+        _ => {}
+    }
+}
+
+pub async fn get_entity_history(entity_id: &str) -> Vec<EntityVersion> {
+    let options = FindOptions::builder()
+        .sort(doc! { "replace_time": 1 })
+        .build();
+    return get_collection::<EntityVersion>()
+        .await
+        .find(doc! { "entity_id": entity_id }, options)
+        .await
+        .expect("unable to get entity versions")
+        .map(|item| item.expect("unable unwrap entity version from cursor stream"))
+        .collect()
+        .await;
+}
+
+pub async fn insert_engagement_snapshots(snapshots: &Vec<EngagementSnapshot>) {
+    insert_if_not_empty::<EngagementSnapshot>(snapshots, DATABASE::MANSA, DATABASE_COLLECTIONS::ENGAGEMENT).await;
+}
+
+pub async fn get_engagement_trajectory(entity_id: &str) -> Vec<EngagementSnapshot> {
+    let options = FindOptions::builder()
+        .sort(doc! { "snapshot_time": 1 })
+        .build();
+    return get_collection::<EngagementSnapshot>()
+        .await
+        .find(doc! { "entity_id": entity_id }, options)
+        .await
+        .expect("unable to get engagement snapshots")
+        .map(|item| item.expect("unable unwrap engagement snapshot from cursor stream"))
+        .collect()
+        .await;
+}
+
+pub async fn get_thread(post_id: &str) -> Option<EntityThread> {
+    let match_query = doc! {
+        "$or": [
+            { "id": post_id },
+            { "root_id": post_id }
+        ]
+    };
+    let entities: Vec<Entity> = ENTITY_COLLECTION
+        .get()
+        .await
+        .find(match_query, None)
+        .await
+        .expect("unable to get thread entities")
+        .map(|item| item.expect("unable unwrap entity from cursor stream"))
+        .collect()
+        .await;
+
+    let mut post: Option<Entity> = None;
+    let mut replies: HashMap<String, Vec<Entity>> = HashMap::new();
+    for entity in entities.into_iter() {
+        match (&entity.entity_type, &entity.parent_id) {
+            (EntityType::Post, _) => post = Some(entity),
+            (_, Some(parent_id)) => replies.entry(parent_id.clone()).or_default().push(entity),
+            _ => {}
+        }
+    }
+    return post.map(|post| build_thread(post, &mut replies));
+}
+
+fn build_thread(entity: Entity, replies: &mut HashMap<String, Vec<Entity>>) -> EntityThread {
+    let mut children = replies.remove(&entity.id).unwrap_or_default();
+    children.sort_by_key(|item| item.date_time);
+    return EntityThread {
+        entity,
+        replies: children
+            .into_iter()
+            .map(|item| build_thread(item, replies))
+            .collect()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::DateTime;
+
+    use crate::commons::{entity::ContentStatus, social_network::SocialNetworkEnum};
+
+    use super::*;
+
+    fn get_entity(id: &str, entity_type: EntityType, parent_id: Option<&str>, millis: i64) -> Entity {
+        return Entity {
+            _id: None,
+            entity_type,
+            date_time: DateTime::from_millis(millis),
+            id: id.to_string(),
+            source: String::from("r/rust"),
+            source_followers: None,
+            author_id: None,
+            title: None,
+            content: None,
+            author_name: None,
+            social_network: SocialNetworkEnum::Reddit,
+            rating: None,
+            images: Vec::new(),
+            content_status: ContentStatus::Active,
+            parent_id: parent_id.map(String::from),
+            root_id: Some(String::from("post")),
+            depth: None,
+            extension: None
+        };
+    }
+
+    fn get_ids(thread: &EntityThread) -> Vec<&str> {
+        return thread.replies.iter().map(|reply| reply.entity.id.as_str()).collect();
+    }
+
+    #[test]
+    fn replies_are_nested_and_sorted_by_time() {
+        let mut replies: HashMap<String, Vec<Entity>> = HashMap::new();
+        for comment in [
+            get_entity("late", EntityType::Comment, Some("post"), 2000),
+            get_entity("early", EntityType::Comment, Some("post"), 1000),
+            get_entity("reply", EntityType::Comment, Some("late"), 3000)
+        ] {
+            replies.entry(comment.parent_id.clone().unwrap()).or_default().push(comment);
+        }
+        let thread = build_thread(get_entity("post", EntityType::Post, None, 0), &mut replies);
+        assert_eq!(get_ids(&thread), vec!["early", "late"]);
+        assert!(thread.replies[0].replies.is_empty());
+        assert_eq!(get_ids(&thread.replies[1]), vec!["reply"]);
+        assert!(replies.is_empty());
+    }
+
+    #[test]
+    fn replies_of_missing_parents_are_left() {
+        let mut replies: HashMap<String, Vec<Entity>> = HashMap::new();
+        replies.insert(String::from("missing"), vec![get_entity("orphan", EntityType::Comment, Some("missing"), 1000)]);
+        let thread = build_thread(get_entity("post", EntityType::Post, None, 0), &mut replies);
+        assert!(thread.replies.is_empty());
+        assert!(replies.contains_key("missing"));
+    }
+}
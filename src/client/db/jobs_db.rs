@@ -0,0 +1,158 @@
+use futures::StreamExt;
+use mongodb::{bson::{doc, oid::ObjectId, DateTime, Document}, options::{FindOneOptions, FindOptions}};
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    commons::{job::{Job, JobStatus, JobProgress}, parsing_tasks::{ParsingTask, ParsingTaskStatus}, social_network::SocialNetworkEnum},
+    utils::time::get_timestamp
+};
+
+use super::client::get_collection;
+
+pub async fn create_job(job: &Job) -> ObjectId {
+    return get_collection::<Job>()
+        .await
+        .insert_one(job, None)
+        .await
+        .expect("unable to create job")
+        .inserted_id
+        .as_object_id()
+        .expect("job id should be object id");
+}
+
+//latest job of the seed which is not cancelled
+pub async fn get_seed_job(social_network: SocialNetworkEnum, seed: &str) -> Option<ObjectId> {
+    let match_query = doc! {
+        "social_network": social_network.to_string(),
+        "seed": seed,
+        "status": {
+            "$in": [JobStatus::Active.to_string(), JobStatus::Paused.to_string()]
+        }
+    };
+    let options = FindOneOptions::builder()
+        .sort(doc! {"creation_time": -1})
+        .build();
+    return get_collection::<Job>()
+        .await
+        .find_one(match_query, options)
+        .await
+        .expect("unable to get seed job")
+        .and_then(|job| job._id);
+}
+
+pub async fn get_jobs() -> Vec<Job> {
+    return get_collection::<Job>()
+        .await
+        .find(None, None)
+        .await
+        .expect("unable to get jobs")
+        .map(|item| item.expect("unable unwrap job from cursor stream"))
+        .collect()
+        .await;
+}
+
+//returns false when job was not in the expected status
+pub async fn update_job_status(id: ObjectId, from: JobStatus, to: JobStatus) -> bool {
+    let match_query = doc! {
+        "_id": id,
+        "status": from.to_string()
+    };
+    let update_query = doc! {
+        "$set": {
+            "status": to.to_string(),
+            "update_time": DateTime::from_millis(get_timestamp() as i64)
+        }
+    };
+    return get_collection::<Job>()
+        .await
+        .update_one(match_query, update_query, None)
+        .await
+        .expect("unable to update job status")
+        .modified_count > 0;
+}
+
+//tasks of paused jobs and jobs cancelled since the time are not claimed,
+//tasks of jobs cancelled earlier are Cancelled already
+pub async fn get_inactive_job_ids(cancelled_since: u64) -> Vec<ObjectId> {
+    let match_query = doc! {
+        "$or": [
+            {"status": JobStatus::Paused.to_string()},
+            get_cancelled_jobs_query(cancelled_since)
+        ]
+    };
+    return get_job_ids(match_query).await;
+}
+
+pub async fn get_cancelled_job_ids(cancelled_since: u64) -> Vec<ObjectId> {
+    return get_job_ids(get_cancelled_jobs_query(cancelled_since)).await;
+}
+
+fn get_cancelled_jobs_query(cancelled_since: u64) -> Document {
+    return doc! {
+        "status": JobStatus::Cancelled.to_string(),
+        "update_time": {
+            "$gte": DateTime::from_millis(cancelled_since as i64)
+        }
+    };
+}
+
+async fn get_job_ids(match_query: Document) -> Vec<ObjectId> {
+    let options = FindOptions::builder()
+        .projection(doc! {"_id": 1})
+        .build();
+    return get_collection::<Job>()
+        .await
+        .clone_with_type::<Document>()
+        .find(match_query, options)
+        .await
+        .expect("unable to get jobs")
+        .filter_map(|item| async move { item.expect("unable unwrap job from cursor stream").get_object_id("_id").ok() })
+        .collect()
+        .await;
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct StatusCount {
+    _id: ParsingTaskStatus,
+    count: u64
+}
+
+pub async fn get_job_progress(id: ObjectId) -> JobProgress {
+    let pipeline = vec![
+        doc! {
+            "$match": {
+                "job": id
+            }
+        },
+        doc! {
+            "$group": {
+                "_id": "$status",
+                "count": {
+                    "$sum": 1
+                }
+            }
+        }
+    ];
+    let counts: Vec<StatusCount> = get_collection::<ParsingTask>()
+        .await
+        .aggregate(pipeline, None)
+        .await
+        .expect("unable to get job progress")
+        .with_type::<StatusCount>()
+        .map(|item| item.expect("unable unwrap job progress from cursor stream"))
+        .collect()
+        .await;
+    let mut progress = JobProgress::default();
+    for count in counts.into_iter() {
+        match count._id {
+            ParsingTaskStatus::New => progress.pending += count.count,
+            ParsingTaskStatus::Processing => progress.processing += count.count,
+            ParsingTaskStatus::Processed |
+            ParsingTaskStatus::Duplicate => progress.processed += count.count,
+            ParsingTaskStatus::Failed |
+            ParsingTaskStatus::DeadLetter => progress.failed += count.count,
+            ParsingTaskStatus::Cancelled => progress.cancelled += count.count
+        }
+    }
+    return progress;
+}
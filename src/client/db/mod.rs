@@ -1,3 +1,7 @@
 pub mod client;
 pub mod tasks_db;
-pub mod entities_db;
\ No newline at end of file
+pub mod entities_db;
+pub mod communities_db;
+pub mod jobs_db;
+pub mod controls_db;
+pub mod workers_db;
\ No newline at end of file
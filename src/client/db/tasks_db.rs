@@ -1,6 +1,8 @@
-use std::{collections::HashMap, hash::Hash};
+use std::{collections::{HashMap, HashSet}, hash::Hash};
 
-use mongodb::{bson::{doc, self, oid::ObjectId}};
+use log::{info, error};
+
+use mongodb::{bson::{doc, self, oid::ObjectId, Document}, options::{UpdateOptions, IndexOptions, FindOptions}, IndexModel};
 use serde::{Serialize, Deserialize};
 
 use crate::{
@@ -8,94 +10,501 @@ use crate::{
         social_network::SocialNetworkEnum, 
         parsing_tasks::{
             ParsingTask, 
-            ParsingTaskStatus
+            ParsingTaskStatus,
+            TaskLease,
+            TaskRetry,
+            TaskFailure,
+            RetryPolicy
         }
     }, 
     utils::time::get_timestamp
 };
 
-use super::client::{DATABASE, DATABASE_COLLECTIONS, insert_if_not_empty, get_collection, GroupBoundaries};
+use super::client::{get_collection, is_duplicate_key_error, GroupBoundaries};
 
-use futures::{StreamExt};
+use futures::{StreamExt, future::join_all};
 
-#[derive(Clone, Copy)]
-pub enum Limit {
-    Limit(u64),
-    NoLimit
+pub async fn create_tasks_indexes() {
+    let indexes = vec![
+        IndexModel::builder()
+            .keys(doc! {"key": 1})
+            .options(IndexOptions::builder().name(Some(String::from("key"))).build())
+            .build(),
+        //at most one New task per key, task being processed may spawn its successor
+        IndexModel::builder()
+            .keys(doc! {"key": 1, "status": 1})
+            .options(
+                IndexOptions::builder()
+                    .name(Some(String::from("pending_key")))
+                    .unique(Some(true))
+                    .partial_filter_expression(Some(doc! {
+                        "status": ParsingTaskStatus::New.to_string(),
+                        "key": {"$exists": true}
+                    }))
+                    .build()
+            )
+            .build(),
+        IndexModel::builder()
+            .keys(doc! {"status": 1, "priority": -1, "execution_time": 1})
+            .options(IndexOptions::builder().name(Some(String::from("claim"))).build())
+            .build()
+    ];
+    get_collection::<ParsingTask>()
+        .await
+        .create_indexes(indexes, None)
+        .await
+        .expect("unable to create parsing tasks indexes, New tasks with the same key should be removed");
 }
 
+//task is skipped when a task with the same key is New,
+//one-shot tasks are skipped when they were ever inserted
 pub async fn insert_tasks(tasks: &Vec<ParsingTask>) {
-    insert_if_not_empty::<ParsingTask>(tasks, DATABASE::MANSA, DATABASE_COLLECTIONS::PARSING_TASKS).await;
+    let mut keys = HashSet::new();
+    let handlers = tasks
+        .iter()
+        .map(|task| (task.get_key(), task))
+        .filter(|(key, _)| keys.insert(key.clone()))
+        .map(|(key, task)| insert_task_if_absent(key, task))
+        .collect::<Vec<_>>();
+    let inserted = join_all(handlers).await.into_iter().filter(|inserted| *inserted).count();
+    info!("inserted {} of {} parsing tasks", inserted, tasks.len());
 }
 
-pub async fn update_task_with_status(id: ObjectId, status: ParsingTaskStatus) {
-    let match_query = doc! {
-        "id" : {
-            "$eq" : id
+async fn insert_task_if_absent(key: String, task: &ParsingTask) -> bool {
+    let mut match_query = doc! {
+        "key": key.clone()
+    };
+    if !task.parameters.is_one_shot() {
+        match_query.insert("status", ParsingTaskStatus::New.to_string());
+    }
+    let mut document = bson::to_document(task).unwrap();
+    document.insert("key", key);
+    let update_query = doc! {
+        "$setOnInsert": document
+    };
+    let options = UpdateOptions::builder()
+        .upsert(Some(true))
+        .build();
+    let result = get_collection::<ParsingTask>()
+        .await
+        .update_one(match_query, update_query, options)
+        .await;
+    return match result {
+        Ok(result) => result.upserted_id.is_some(),
+        //concurrent insert of the same task won
+        Err(e) if is_duplicate_key_error(&e) => false,
+        Err(e) => {
+            error!("unable to insert parsing task: {}", e);
+            false
         }
     };
+}
+
+//task returned to New is marked Duplicate when an equal task is already New
+async fn return_task_to_new(match_query: Document, update_query: Document) -> u64 {
+    let collection = get_collection::<ParsingTask>().await;
+    return match collection.update_one(match_query.clone(), update_query, None).await {
+        Ok(result) => result.modified_count,
+        Err(e) if is_duplicate_key_error(&e) => {
+            let duplicate_query = doc! {
+                "$set": {
+                    "status": ParsingTaskStatus::Duplicate.to_string()
+                },
+                "$unset": {
+                    "lease": ""
+                }
+            };
+            collection
+                .update_one(match_query, duplicate_query, None)
+                .await
+                .expect("unable to mark duplicate task")
+                .modified_count
+        },
+        Err(e) => panic!("unable to return task to New: {}", e)
+    };
+}
+
+async fn get_task_ids(match_query: Document, options: FindOptions) -> Vec<ObjectId> {
+    let mut options = options;
+    options.projection = Some(doc! {"_id": 1});
+    return get_collection::<ParsingTask>()
+        .await
+        .clone_with_type::<Document>()
+        .find(match_query, options)
+        .await
+        .expect("unable to get parsing tasks")
+        .filter_map(|item| async move { item.expect("unable unwrap parsing task from cursor stream").get_object_id("_id").ok() })
+        .collect()
+        .await;
+}
+
+//tasks are returned one by one, so a duplicate does not stop the others
+async fn return_tasks_to_new(match_query: Document, update_query: Document) -> u64 {
+    let ids = get_task_ids(match_query.clone(), FindOptions::default()).await;
+    let mut returned = 0;
+    for id in ids.into_iter() {
+        let mut task_query = match_query.clone();
+        task_query.insert("_id", id);
+        returned += return_task_to_new(task_query, update_query.clone()).await;
+    }
+    return returned;
+}
+
+//claimed task which is still leased by the worker that claimed it
+fn get_leased_task_query(task: &ParsingTask) -> Document {
+    return doc! {
+        "_id": task._id.unwrap(),
+        "status": ParsingTaskStatus::Processing.to_string(),
+        "lease.owner": task.lease.as_ref().map(|lease| lease.owner.clone())
+    };
+}
+
+//returns false when the lease was lost and the task is owned by another worker
+pub async fn update_task_with_status(task: &ParsingTask, status: ParsingTaskStatus) -> bool {
     let update_query = doc! {
         "$set": {
             "status": status.to_string()
+        },
+        "$unset": {
+            "lease": ""
         }
     };
-    get_collection::<ParsingTask>()
+    if status == ParsingTaskStatus::New {
+        return return_task_to_new(get_leased_task_query(task), update_query).await > 0;
+    }
+    return get_collection::<ParsingTask>()
         .await
-        .update_one(match_query, update_query, None)
+        .update_one(get_leased_task_query(task), update_query, None)
         .await
-        .expect("unable to update tasks");
+        .expect("unable to update task")
+        .modified_count > 0;
 }
 
-pub async fn update_tasks_with_status(ids: Vec<bson::oid::ObjectId>, status: ParsingTaskStatus) {
-    let match_query = doc! {
-        "_id" : {
-            "$in" : ids
+//social network, seed and priority of tasks, unit of fair scheduling
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
+pub struct TaskGroup {
+    pub social_network: SocialNetworkEnum,
+    pub seed: Option<String>,
+    pub priority: i64
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct TaskGroupId {
+    _id: TaskGroup
+}
+
+fn get_due_tasks_query(now: u64, inactive_jobs: &[ObjectId]) -> Document {
+    return doc! {
+        "status": ParsingTaskStatus::New.to_string(),
+        "execution_time": {
+            "$lt": now as i64
+        },
+        "$or": [
+            {"retry.next_attempt_time": null},
+            {"retry.next_attempt_time": {"$lt": now as i64}}
+        ],
+        "job": {
+            "$nin": inactive_jobs
         }
     };
+}
+
+//groups having due tasks, tasks of paused and cancelled jobs are skipped
+pub async fn get_due_task_groups(inactive_jobs: &[ObjectId]) -> Vec<TaskGroup> {
+    let pipeline = vec![
+        doc! {
+            "$match": get_due_tasks_query(get_timestamp(), inactive_jobs)
+        },
+        doc! {
+            "$group": {
+                "_id": {
+                    "social_network": "$social_network",
+                    "seed": "$seed.name",
+                    "priority": "$priority"
+                }
+            }
+        }
+    ];
+    return get_collection::<ParsingTask>()
+        .await
+        .aggregate(pipeline, None)
+        .await
+        .expect("unable to get task groups")
+        .with_type::<TaskGroupId>()
+        .map(|item| item.expect("unable unwrap task group from cursor stream")._id)
+        .collect()
+        .await;
+}
+
+//due tasks of the group are marked Processing under the lease. update matches only due tasks,
+//so a task picked by concurrent workers is claimed by one of them
+pub async fn claim_tasks(owner: &str, lease_millis: u64, inactive_jobs: &[ObjectId], group: &TaskGroup, limit: usize) -> Vec<ParsingTask> {
+    let now = get_timestamp();
+    let mut match_query = get_due_tasks_query(now, inactive_jobs);
+    match_query.insert("social_network", group.social_network.to_string());
+    match_query.insert("seed.name", group.seed.clone());
+    match_query.insert("priority", group.priority);
+    let options = FindOptions::builder()
+        .sort(doc! {"priority": -1, "execution_time": 1})
+        .limit(Some(limit as i64))
+        .build();
+    let ids = get_task_ids(match_query.clone(), options).await;
+    if ids.is_empty() {
+        return Vec::new();
+    }
+    match_query.insert("_id", doc! {"$in": ids.clone()});
+    let lease = TaskLease { 
+        owner: owner.to_string(), 
+        expires_at: now + lease_millis,
+        account: None
+    };
     let update_query = doc! {
         "$set": {
-            "status": status.to_string()
+            "status": ParsingTaskStatus::Processing.to_string(),
+            "lease": bson::to_bson(&lease).unwrap()
+        }
+    };
+    let collection = get_collection::<ParsingTask>().await;
+    collection
+        .update_many(match_query, update_query, None)
+        .await
+        .expect("unable to claim parsing tasks");
+    let claimed_query = doc! {
+        "_id": {
+            "$in": ids
+        },
+        "status": ParsingTaskStatus::Processing.to_string(),
+        "lease.owner": owner
+    };
+    let options = FindOptions::builder()
+        .sort(doc! {"priority": -1, "execution_time": 1})
+        .build();
+    return collection
+        .find(claimed_query, options)
+        .await
+        .expect("unable to get claimed parsing tasks")
+        .map(|item| item.expect("unable unwrap parsing task from cursor stream"))
+        .collect()
+        .await;
+}
+
+//lease time starts again when parser takes the task from the channel
+pub async fn start_task_lease(id: ObjectId, owner: &str, account: String, lease_millis: u64) {
+    let match_query = doc! {
+        "_id": id,
+        "lease.owner": owner
+    };
+    let update_query = doc! {
+        "$set": {
+            "lease.account": account,
+            "lease.expires_at": (get_timestamp() + lease_millis) as i64
         }
     };
     get_collection::<ParsingTask>()
         .await
-        .update_many(match_query, update_query, None)
+        .update_one(match_query, update_query, None)
         .await
-        .expect("unable to update tasks");
+        .inspect_err(|e| error!("unable to start lease of task {}: {}", id, e))
+        .ok();
+}
+
+//transient failure is retried until max attempts, then task goes to DeadLetter.
+//rate limited task is retried without counting an attempt.
+//returns None when the lease was lost
+pub async fn fail_task(task: &ParsingTask, failure: TaskFailure, retry_policy: &RetryPolicy) -> Option<ParsingTaskStatus> {
+    let attempts = match failure {
+        TaskFailure::RateLimited(_) => task.retry.attempts,
+        _ => task.retry.attempts + 1
+    };
+    let (status, error) = match failure {
+        TaskFailure::Permanent(error) => (ParsingTaskStatus::Failed, error),
+        TaskFailure::RateLimited(error) => (ParsingTaskStatus::New, error),
+        TaskFailure::Transient(error) if attempts >= retry_policy.max_attempts => (ParsingTaskStatus::DeadLetter, error),
+        TaskFailure::Transient(error) => (ParsingTaskStatus::New, error)
+    };
+    let retry = TaskRetry {
+        attempts,
+        last_error: Some(error),
+        next_attempt_time: match status {
+            ParsingTaskStatus::New => Some(retry_policy.get_next_attempt_time(attempts, get_timestamp())),
+            _ => None
+        }
+    };
+    let update_query = doc! {
+        "$set": {
+            "status": status.to_string(),
+            "retry": bson::to_bson(&retry).unwrap()
+        },
+        "$unset": {
+            "lease": ""
+        }
+    };
+    let updated = if status == ParsingTaskStatus::New {
+        return_task_to_new(get_leased_task_query(task), update_query).await
+    } else {
+        get_collection::<ParsingTask>()
+            .await
+            .update_one(get_leased_task_query(task), update_query, None)
+            .await
+            .expect("unable to update failed task")
+            .modified_count
+    };
+    return (updated > 0).then_some(status);
 }
 
-pub async fn get_tasks_sorted_by_exec_time(statuses: Vec<ParsingTaskStatus>, limit: Limit) -> Vec<ParsingTask> {
+pub async fn get_tasks_with_status(statuses: Vec<ParsingTaskStatus>) -> Vec<ParsingTask> {
     let statuses: Vec<String> = statuses.into_iter().map(|item| item.to_string()).collect();
     let match_query = doc! {
-        "$match": {
-            "status": {
-                "$in": statuses
-            },
-            "execution_time": {
-                "$lt": get_timestamp() as i64
-            }
-        } 
-    };
-    let sort_query = doc! {
-        "$sort": {
-            "execution_time": 1
+        "status": {
+            "$in": statuses
         }
     };
-    let pipeline_fetch = match limit {
-        Limit::Limit(number) => vec![match_query, sort_query, doc! {"$limit": number as i64}],
-        Limit::NoLimit => vec![match_query, sort_query],
-    }; 
-    return  get_collection::<ParsingTask>()
+    let options = FindOptions::builder()
+        .sort(doc! {"execution_time": 1})
+        .build();
+    return get_collection::<ParsingTask>()
         .await
-        .aggregate(pipeline_fetch, None)
+        .find(match_query, options)
         .await
         .expect("unable to get parsing tasks")
-        .with_type::<ParsingTask>()
         .map(|item| item.expect("unable unwrap parsing task from cursor stream"))
         .collect()
         .await;
 }
 
+//failed tasks are queued again with reset attempts
+pub async fn requeue_tasks(statuses: Vec<ParsingTaskStatus>) -> u64 {
+    let statuses: Vec<String> = statuses.into_iter().map(|item| item.to_string()).collect();
+    let match_query = doc! {
+        "status": {
+            "$in": statuses
+        }
+    };
+    let update_query = doc! {
+        "$set": {
+            "status": ParsingTaskStatus::New.to_string(),
+            "retry": bson::to_bson(&TaskRetry::default()).unwrap()
+        }
+    };
+    return return_tasks_to_new(match_query, update_query).await;
+}
+
+pub async fn cancel_job_tasks(job: ObjectId) -> u64 {
+    let match_query = doc! {
+        "job": job,
+        "status": ParsingTaskStatus::New.to_string()
+    };
+    let update_query = doc! {
+        "$set": {
+            "status": ParsingTaskStatus::Cancelled.to_string()
+        }
+    };
+    return get_collection::<ParsingTask>()
+        .await
+        .update_many(match_query, update_query, None)
+        .await
+        .expect("unable to cancel job tasks")
+        .modified_count;
+}
+
+//New tasks with the keys are moved to the job
+pub async fn assign_tasks_job(keys: Vec<String>, job: ObjectId) -> u64 {
+    let match_query = doc! {
+        "key": {
+            "$in": keys
+        },
+        "status": ParsingTaskStatus::New.to_string()
+    };
+    let update_query = doc! {
+        "$set": {
+            "job": job
+        }
+    };
+    return get_collection::<ParsingTask>()
+        .await
+        .update_many(match_query, update_query, None)
+        .await
+        .expect("unable to assign tasks job")
+        .modified_count;
+}
+
+//number of tasks of the seed matching the filter which were due since the timestamp
+pub async fn count_seed_tasks_since(seed: &str, filter: Document, since: u64) -> u64 {
+    let mut match_query = doc! {
+        "seed.name": seed,
+        "execution_time": {
+            "$gte": since as i64
+        }
+    };
+    match_query.extend(filter);
+    return get_collection::<ParsingTask>()
+        .await
+        .count_documents(match_query, None)
+        .await
+        .expect("unable to count seed tasks");
+}
+
+//leases of the tasks a live worker has queued or running never expire
+pub async fn renew_worker_leases(owner: &str, ids: &[ObjectId], lease_millis: u64) -> u64 {
+    if ids.is_empty() {
+        return 0;
+    }
+    let match_query = doc! {
+        "_id": {
+            "$in": ids
+        },
+        "status": ParsingTaskStatus::Processing.to_string(),
+        "lease.owner": owner
+    };
+    let update_query = doc! {
+        "$set": {
+            "lease.expires_at": (get_timestamp() + lease_millis) as i64
+        }
+    };
+    return get_collection::<ParsingTask>()
+        .await
+        .update_many(match_query, update_query, None)
+        .await
+        .expect("unable to renew worker leases")
+        .modified_count;
+}
+
+//returns tasks claimed by the worker to New, used on shutdown
+pub async fn release_worker_leases(owner: &str) -> u64 {
+    let match_query = doc! {
+        "status": ParsingTaskStatus::Processing.to_string(),
+        "lease.owner": owner
+    };
+    let update_query = doc! {
+        "$set": {
+            "status": ParsingTaskStatus::New.to_string()
+        },
+        "$unset": {
+            "lease": ""
+        }
+    };
+    return return_tasks_to_new(match_query, update_query).await;
+}
+
+//returns tasks of expired leases and Processing tasks without lease to New
+pub async fn release_expired_leases() -> u64 {
+    let match_query = doc! {
+        "status": ParsingTaskStatus::Processing.to_string(),
+        "$or": [
+            {"lease.expires_at": {"$lt": get_timestamp() as i64}},
+            {"lease": null}
+        ]
+    };
+    let update_query = doc! {
+        "$set": {
+            "status": ParsingTaskStatus::New.to_string()
+        },
+        "$unset": {
+            "lease": ""
+        }
+    };
+    return return_tasks_to_new(match_query, update_query).await;
+}
 
 #[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
 pub struct GroupedTasks {
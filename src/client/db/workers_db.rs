@@ -0,0 +1,109 @@
+use futures::StreamExt;
+use log::error;
+use mongodb::{bson::doc, options::{ReplaceOptions, UpdateOptions}, error::Error};
+
+use crate::{client::parser_v2::worker::{Worker, LeaderLease}, utils::time::get_timestamp};
+
+use super::client::{get_collection, is_duplicate_key_error};
+
+pub async fn update_worker(worker: &Worker) {
+    let options = ReplaceOptions::builder()
+        .upsert(Some(true))
+        .build();
+    get_collection::<Worker>()
+        .await
+        .replace_one(doc! {"_id": worker._id.clone()}, worker, options)
+        .await
+        .inspect_err(|e| error!("unable to update worker {}: {}", worker._id, e))
+        .ok();
+}
+
+pub async fn get_workers() -> Vec<Worker> {
+    return get_collection::<Worker>()
+        .await
+        .find(None, None)
+        .await
+        .expect("unable to get workers")
+        .map(|item| item.expect("unable unwrap worker from cursor stream"))
+        .collect()
+        .await;
+}
+
+pub async fn remove_worker(id: &str) {
+    get_collection::<Worker>()
+        .await
+        .delete_one(doc! {"_id": id}, None)
+        .await
+        .expect("unable to remove worker");
+}
+
+//takes the role when it is free or expired, or extends the lease of the current holder.
+//returns false when the role is held by another worker
+pub async fn acquire_leadership(role: &str, worker_id: &str, lease_millis: u64) -> Result<bool, Error> {
+    let now = get_timestamp();
+    let match_query = doc! {
+        "_id": role,
+        "$or": [
+            {"holder": worker_id},
+            {"expires_at": {"$lt": now as i64}}
+        ]
+    };
+    let update_query = doc! {
+        "$set": {
+            "holder": worker_id,
+            "expires_at": (now + lease_millis) as i64
+        }
+    };
+    let options = UpdateOptions::builder()
+        .upsert(Some(true))
+        .build();
+    let result = get_collection::<LeaderLease>()
+        .await
+        .update_one(match_query, update_query, options)
+        .await;
+    return match result {
+        Ok(_) => Ok(true),
+        //role document exists and is held by another worker, so upsert conflicts with it
+        Err(e) if is_duplicate_key_error(&e) => Ok(false),
+        Err(e) => Err(e)
+    };
+}
+
+//lease is expired instead of removed, so marker of the role is kept
+pub async fn release_leadership(role: &str, worker_id: &str) {
+    let update_query = doc! {
+        "$set": {
+            "expires_at": 0_i64
+        }
+    };
+    get_collection::<LeaderLease>()
+        .await
+        .update_one(doc! {"_id": role, "holder": worker_id}, update_query, None)
+        .await
+        .inspect_err(|e| error!("unable to release leadership of {}: {}", role, e))
+        .ok();
+}
+
+pub async fn get_leader_marker(role: &str) -> Option<String> {
+    return get_collection::<LeaderLease>()
+        .await
+        .find_one(doc! {"_id": role}, None)
+        .await
+        .expect("unable to get leader lease")
+        .and_then(|lease| lease.marker);
+}
+
+//marker is set only while the worker holds the role
+pub async fn set_leader_marker(role: &str, worker_id: &str, marker: &str) -> bool {
+    let update_query = doc! {
+        "$set": {
+            "marker": marker
+        }
+    };
+    return get_collection::<LeaderLease>()
+        .await
+        .update_one(doc! {"_id": role, "holder": worker_id}, update_query, None)
+        .await
+        .expect("unable to set leader marker")
+        .modified_count > 0;
+}
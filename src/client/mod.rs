@@ -1,4 +1,5 @@
 pub mod http_client;
 pub mod settings;
 pub mod db;
-pub mod parser_v2;
\ No newline at end of file
+pub mod parser_v2;
+pub mod commands;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use std::{sync::Arc};
+use std::{sync::Arc, error::Error};
 
 use derivative::Derivative;
 use log::{info, error};
@@ -65,6 +65,12 @@ impl Account {
     }
     
     pub async fn auth(&self) 
+    {
+        self.try_auth().await.unwrap();
+    }
+
+    //failed authentication is returned instead of panic, so it can be handled during parsing
+    pub async fn try_auth(&self) -> Result<(), Box<dyn Error + Send + Sync>>
     {
 
         info!("authenticate account {:?}", self.account_data);
@@ -83,12 +89,17 @@ impl Account {
                     self.account_data.password.as_ref().unwrap_or(&"no password for that social net".to_string()), 
                     self.account_data.social_network.to_string()
                 )
-            ).unwrap();
+            )?;
 
         let mut session_guard = self.session.write().await;
         session_guard.replace(session);
         STATISTICS.increase_total_number_of_accounts();
+        return Ok(());
+    }
 
+    pub fn set_proxy(&mut self, proxy: Option<ProxyPtr>) {
+        self.proxy = proxy;
+        self.setup_reqwest_client();
     }
 
     pub fn setup_reqwest_client(&mut self) {
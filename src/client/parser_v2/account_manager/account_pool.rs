@@ -57,6 +57,33 @@ impl AccountPool {
         return account_clone;
     }
 
+    pub async fn get_account_ids(&self) -> Vec<String> {
+        let mut ids = Vec::new();
+        for accounts in self.accounts_queue.values() {
+            ids.extend(accounts.lock().await.iter().map(|account| account.account_data.get_id()));
+        }
+        return ids;
+    }
+
+    //requests accounts of the network can make now. account with passed refresh time
+    //is counted as one request, its new limit is known after the response
+    pub async fn get_request_budget(&self, social_network: SocialNetworkEnum) -> usize {
+        let now = get_timestamp();
+        let mut budget = 0;
+        if let Some(accounts) = self.accounts_queue.get(&social_network) {
+            for account in accounts.lock().await.iter() {
+                if let Some(session) = account.session.read().await.as_ref() {
+                    budget += if session.retrieve_timestamp + session.millis_to_refresh <= now {
+                        session.requests_limit.max(1)
+                    } else {
+                        session.requests_limit
+                    };
+                }
+            }
+        }
+        return budget;
+    }
+
     pub async fn move_end<'a>(
         &self, 
         account: AccountPtr, 
@@ -25,7 +25,8 @@ use super::{
         AccountPoolPtr, 
         AccountPtr
     }, 
-    account_pool::AccountPool
+    account_pool::AccountPool,
+    proxy_assignment::assign_proxies
 };
 
 pub struct AccountPoolBuilder {
@@ -42,19 +43,24 @@ impl AccountPoolBuilder {
          }
     }
  
-    async fn read_accounts_from_settings(&mut self) {
+    async fn read_accounts_from_settings(&mut self) -> Result<(), String> {
+        let mut global_proxies_offset = 0;
         for social_network in self.settings.social_network_settings.keys() {
 
-            let accounts: LinkedList<AccountPtr> = SOCIAL_NETWORKS.get(&social_network)
+            let mut accounts = SOCIAL_NETWORKS.get(&social_network)
                 .expect("No such social network!")
-                .prepare_accounts(self.settings.clone()).unwrap()
+                .prepare_accounts(self.settings.clone()).unwrap();
+
+            assign_proxies(&mut accounts, *social_network, &self.settings.general_settings, &mut global_proxies_offset)?;
+
+            let accounts: LinkedList<AccountPtr> = accounts
                 .into_iter()
                 .map(|account| Arc::new(account))
                 .collect();
 
             info!("start account authorization");
 
-            tokio_stream::iter(accounts.iter()).for_each_concurrent(8, |account| async {
+            tokio_stream::iter(accounts.iter()).for_each_concurrent(self.settings.general_settings.concurrency.auth_concurrency, |account| async {
                 account.auth().await;
             }).await;
 
@@ -65,10 +71,11 @@ impl AccountPoolBuilder {
                 Mutex::new(accounts)
             );
         }
+        return Ok(());
     }
 
-    pub async fn build(mut self) -> AccountPoolPtr {
-        self.read_accounts_from_settings().await;
-        return AccountPool::new(self.social_network_accounts_map).await;
+    pub async fn build(mut self) -> Result<AccountPoolPtr, String> {
+        self.read_accounts_from_settings().await?;
+        return Ok(AccountPool::new(self.social_network_accounts_map).await);
     }
 }
@@ -1,3 +1,4 @@
 pub mod account_pool_builder;
 pub mod account_pool;
-pub mod account;
\ No newline at end of file
+pub mod account;
+pub mod proxy_assignment;
\ No newline at end of file
@@ -0,0 +1,46 @@
+use log::info;
+
+use crate::{
+    client::settings::{GeneralSettings, ProxyAssignment},
+    commons::social_network::SocialNetworkEnum
+};
+
+use super::account::Account;
+
+//proxies are not assigned when disable_proxy is set or the pool of the network is empty.
+//networks without own pool share the global one, global_offset is the number of its proxies
+//given to accounts of previous networks, so they do not all start from the first proxy
+pub fn assign_proxies(
+    accounts: &mut Vec<Account>, 
+    social_network: SocialNetworkEnum, 
+    general_settings: &GeneralSettings, 
+    global_offset: &mut usize
+) -> Result<(), String> {
+    if general_settings.disable_proxy {
+        info!("proxies are disabled, {} accounts of {} use direct connection", accounts.len(), social_network);
+        return Ok(());
+    }
+    let (proxies, offset) = match general_settings.social_network_proxies.get(&social_network) {
+        Some(proxies) => (proxies, 0),
+        None => (&general_settings.proxies, *global_offset)
+    };
+    if proxies.is_empty() {
+        info!("no proxies for {}, {} accounts use direct connection", social_network, accounts.len());
+        return Ok(());
+    }
+    if general_settings.proxy_assignment == ProxyAssignment::Sticky && proxies.len() < offset + accounts.len() {
+        return Err(format!(
+            "sticky proxy assignment needs a proxy per account: {} has {} accounts and {} free proxies", 
+            social_network, accounts.len(), proxies.len().saturating_sub(offset)
+        ));
+    }
+    for (i, account) in accounts.iter_mut().enumerate() {
+        let proxy = proxies[(offset + i) % proxies.len()].clone();
+        info!("account {} of {} uses proxy {}", account.account_data.get_id(), social_network, proxy.host);
+        account.set_proxy(Some(Box::new(proxy)));
+    }
+    if !general_settings.social_network_proxies.contains_key(&social_network) {
+        *global_offset += accounts.len();
+    }
+    return Ok(());
+}
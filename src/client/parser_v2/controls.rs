@@ -0,0 +1,121 @@
+use std::{collections::HashMap, sync::{Arc, RwLock}, time::Duration};
+
+use log::info;
+use mongodb::bson::DateTime;
+use serde::{Serialize, Deserialize};
+use strum::{EnumIter, EnumString};
+
+use crate::{
+    client::db::{
+        client::{DBCollection, DATABASE_COLLECTIONS}, 
+        controls_db::get_parser_controls, 
+        tasks_db::TaskGroup
+    }, 
+    commons::{social_network::SocialNetworkEnum, parsing_tasks::ParsingTask},
+    utils::time::get_timestamp
+};
+
+const CONTROLS_REFRESH_INTERVAL_SECONDS: u64 = 5;
+
+//ordered from the least to the most restrictive.
+//draining and paused stop claiming alike, they differ only in tasks already in the channel
+#[derive(Serialize, Deserialize, Clone, Copy, Hash, Eq, PartialEq, PartialOrd, Ord, Debug, EnumIter, EnumString, strum::Display)]
+#[strum(ascii_case_insensitive)]
+pub enum ParserState {
+    //tasks are claimed and processed
+    Running,
+    //no new tasks are claimed, tasks in the channel are still processed
+    Draining,
+    //no new tasks are claimed, tasks in the channel are returned to New without processing
+    Paused
+}
+
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
+pub enum ControlScope {
+    Global,
+    SocialNetwork(SocialNetworkEnum),
+    Seed(String)
+}
+
+//state set by "parser" command, shared by all workers
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ParserControl {
+    pub scope: ControlScope,
+    pub state: ParserState,
+    pub update_time: DateTime
+}
+
+impl ParserControl {
+    pub fn new(scope: ControlScope, state: ParserState) -> ParserControl {
+        return ParserControl { 
+            scope, 
+            state, 
+            update_time: DateTime::from_millis(get_timestamp() as i64) 
+        };
+    }
+}
+
+impl DBCollection for ParserControl {
+    fn get_collection() -> String {
+        return DATABASE_COLLECTIONS::PARSER_CONTROLS.to_string();
+    }
+}
+
+pub type ParserControlsPtr = Arc<ParserControls>;
+
+//local copy of parser controls, refreshed from db
+pub struct ParserControls {
+    states: RwLock<HashMap<ControlScope, ParserState>>
+}
+
+impl ParserControls {
+
+    pub async fn load() -> ParserControlsPtr {
+        let controls = Arc::new(ParserControls { states: RwLock::new(HashMap::new()) });
+        controls.refresh().await;
+        return controls;
+    }
+
+    async fn refresh(&self) {
+        let states: HashMap<ControlScope, ParserState> = get_parser_controls()
+            .await
+            .into_iter()
+            .map(|control| (control.scope, control.state))
+            .collect();
+        let mut current_states = self.states.write().unwrap();
+        if *current_states != states {
+            info!("parser controls changed: {:?}", states);
+            *current_states = states;
+        }
+    }
+
+    pub fn run_refresh(controls: ParserControlsPtr) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(CONTROLS_REFRESH_INTERVAL_SECONDS)).await;
+                controls.refresh().await;
+            }
+        });
+    }
+
+    //the most restrictive of global, social network and seed states
+    pub fn get_state(&self, social_network: SocialNetworkEnum, seed: Option<&String>) -> ParserState {
+        let states = self.states.read().unwrap();
+        let mut scopes = vec![ControlScope::Global, ControlScope::SocialNetwork(social_network)];
+        scopes.extend(seed.map(|seed| ControlScope::Seed(seed.clone())));
+        return scopes
+            .iter()
+            .filter_map(|scope| states.get(scope))
+            .max()
+            .copied()
+            .unwrap_or(ParserState::Running);
+    }
+
+    pub fn get_task_state(&self, task: &ParsingTask) -> ParserState {
+        return self.get_state(task.social_network, task.seed.as_ref().map(|seed| &seed.name));
+    }
+
+    pub fn get_group_state(&self, group: &TaskGroup) -> ParserState {
+        return self.get_state(group.social_network, group.seed.as_ref());
+    }
+}
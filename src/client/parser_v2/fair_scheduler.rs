@@ -0,0 +1,168 @@
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{
+    client::{db::tasks_db::TaskGroup, settings::SchedulingSettings}, 
+    commons::social_network::SocialNetworkEnum
+};
+
+//smooth weighted round robin: every item is picked proportionally to its weight
+//and picks of the same item are spread evenly
+struct WeightedRoundRobin<K> {
+    items: Vec<(K, i64, i64)>
+}
+
+impl<K: Clone + PartialEq> WeightedRoundRobin<K> {
+
+    fn new() -> WeightedRoundRobin<K> {
+        return WeightedRoundRobin { items: Vec::new() };
+    }
+
+    fn add(&mut self, key: K, weight: u64) {
+        self.items.push((key, weight.max(1) as i64, 0));
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.items.retain(|(item, _, _)| item != key);
+    }
+
+    fn is_empty(&self) -> bool {
+        return self.items.is_empty();
+    }
+
+    fn next(&mut self) -> Option<K> {
+        let total: i64 = self.items.iter().map(|(_, weight, _)| weight).sum();
+        for (_, weight, current) in self.items.iter_mut() {
+            *current += *weight;
+        }
+        let (key, _, current) = self.items.iter_mut().max_by_key(|(_, _, current)| *current)?;
+        *current -= total;
+        return Some(key.clone());
+    }
+}
+
+//picks the group of the next claimed task among groups of one priority,
+//first among social networks, then among seeds of the network
+pub struct FairScheduler {
+    social_networks: WeightedRoundRobin<SocialNetworkEnum>,
+    seeds: HashMap<SocialNetworkEnum, WeightedRoundRobin<TaskGroup>>
+}
+
+impl FairScheduler {
+
+    pub fn new(groups: Vec<TaskGroup>, settings: &SchedulingSettings) -> FairScheduler {
+        let mut scheduler = FairScheduler { 
+            social_networks: WeightedRoundRobin::new(), 
+            seeds: HashMap::new() 
+        };
+        for group in groups.into_iter() {
+            let seeds = scheduler.seeds.entry(group.social_network).or_insert_with(|| {
+                scheduler.social_networks.add(group.social_network, get_weight(&settings.social_network_weights, &group.social_network));
+                WeightedRoundRobin::new()
+            });
+            let seed_weight = group.seed.as_ref().map_or(1, |seed| get_weight(&settings.seed_weights, seed));
+            seeds.add(group, seed_weight);
+        }
+        return scheduler;
+    }
+
+    pub fn next(&mut self) -> Option<TaskGroup> {
+        let social_network = self.social_networks.next()?;
+        return self.seeds.get_mut(&social_network)?.next();
+    }
+
+    //group has no more due tasks
+    pub fn remove(&mut self, group: &TaskGroup) {
+        if let Some(seeds) = self.seeds.get_mut(&group.social_network) {
+            seeds.remove(group);
+            if seeds.is_empty() {
+                self.seeds.remove(&group.social_network);
+                self.social_networks.remove(&group.social_network);
+            }
+        }
+    }
+}
+
+fn get_weight<K: Eq + Hash>(weights: &HashMap<K, u64>, key: &K) -> u64 {
+    return weights.get(key).copied().unwrap_or(1);
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_group(social_network: SocialNetworkEnum, seed: &str) -> TaskGroup {
+        return TaskGroup { social_network, seed: Some(seed.to_string()), priority: 0 };
+    }
+
+    fn count_picks(scheduler: &mut FairScheduler, picks: usize) -> HashMap<TaskGroup, usize> {
+        let mut counts = HashMap::new();
+        for _ in 0..picks {
+            *counts.entry(scheduler.next().unwrap()).or_insert(0) += 1;
+        }
+        return counts;
+    }
+
+    #[test]
+    fn picks_are_proportional_to_weights() {
+        let mut wrr = WeightedRoundRobin::new();
+        wrr.add("a", 3);
+        wrr.add("b", 1);
+        let picks: Vec<&str> = (0..40).map(|_| wrr.next().unwrap()).collect();
+        assert_eq!(picks.iter().filter(|pick| **pick == "a").count(), 30);
+        //picks are spread evenly, every window of the total weight has exact shares
+        for window in picks.chunks(4) {
+            assert_eq!(window.iter().filter(|pick| **pick == "b").count(), 1);
+        }
+    }
+
+    #[test]
+    fn zero_weight_is_picked_as_weight_one() {
+        let mut wrr = WeightedRoundRobin::new();
+        wrr.add("a", 0);
+        wrr.add("b", 1);
+        let picks: Vec<&str> = (0..10).map(|_| wrr.next().unwrap()).collect();
+        assert_eq!(picks.iter().filter(|pick| **pick == "a").count(), 5);
+    }
+
+    #[test]
+    fn seeds_share_network_by_weight() {
+        let settings = SchedulingSettings {
+            seed_weights: HashMap::from([(String::from("r/big"), 2)]),
+            ..SchedulingSettings::default()
+        };
+        let big = get_group(SocialNetworkEnum::Reddit, "r/big");
+        let small = get_group(SocialNetworkEnum::Reddit, "r/small");
+        let mut scheduler = FairScheduler::new(vec![big.clone(), small.clone()], &settings);
+        let counts = count_picks(&mut scheduler, 30);
+        assert_eq!(counts[&big], 20);
+        assert_eq!(counts[&small], 10);
+    }
+
+    #[test]
+    fn networks_share_slots_regardless_of_seed_count() {
+        let settings = SchedulingSettings::default();
+        let groups = vec![
+            get_group(SocialNetworkEnum::Reddit, "r/first"),
+            get_group(SocialNetworkEnum::Reddit, "r/second"),
+            get_group(SocialNetworkEnum::Reddit, "r/third"),
+            get_group(SocialNetworkEnum::Twitter, "user")
+        ];
+        let mut scheduler = FairScheduler::new(groups, &settings);
+        let counts = count_picks(&mut scheduler, 60);
+        assert_eq!(counts[&get_group(SocialNetworkEnum::Twitter, "user")], 30);
+        assert_eq!(counts[&get_group(SocialNetworkEnum::Reddit, "r/first")], 10);
+    }
+
+    #[test]
+    fn removed_groups_are_not_picked() {
+        let settings = SchedulingSettings::default();
+        let reddit = get_group(SocialNetworkEnum::Reddit, "r/rust");
+        let twitter = get_group(SocialNetworkEnum::Twitter, "user");
+        let mut scheduler = FairScheduler::new(vec![reddit.clone(), twitter.clone()], &settings);
+        scheduler.remove(&reddit);
+        assert!((0..5).all(|_| scheduler.next() == Some(twitter.clone())));
+        scheduler.remove(&twitter);
+        assert_eq!(scheduler.next(), None);
+    }
+}
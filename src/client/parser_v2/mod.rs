@@ -1,4 +1,8 @@
 pub mod parser;
 pub mod task_publisher;
 pub mod account_manager;
-pub mod statistics;
\ No newline at end of file
+pub mod statistics;
+pub mod shutdown;
+pub mod fair_scheduler;
+pub mod controls;
+pub mod worker;
\ No newline at end of file
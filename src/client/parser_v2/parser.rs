@@ -1,22 +1,33 @@
-use std::{sync::{atomic::AtomicUsize, Arc}};
+use std::{sync::{atomic::AtomicUsize, Arc}, time::Duration, collections::HashMap};
 
-use log::debug;
-use tokio::sync::{mpsc::Receiver, Notify};
+use futures::future::join_all;
+use log::{debug, info, error};
+use strum::IntoEnumIterator;
+use tokio::{sync::{mpsc::Receiver, Notify}, task::JoinHandle};
 
 use crate::{
     commons::{
         parsing_tasks::{
-            ParsingTask
+            ParsingTask,
+            ParsingTaskStatus,
+            RetryPolicy,
+            TaskFailure
         }, 
         social_network::{
-            SOCIAL_NETWORKS
+            SOCIAL_NETWORKS,
+            SocialNetworkEnum
         }
+    },
+    client::{
+        db::tasks_db::{release_worker_leases, update_task_with_status, start_task_lease, fail_task},
+        db::workers_db::remove_worker,
+        settings::{SettingsPtr, ConcurrencySettings, read_general_settings}
     }
 };
 
 use super::{
     task_publisher::{
-        TaskPublisherBuilder, TaskPublisherPtr
+        TaskPublisherBuilder, TaskPublisherPtr, TaskPublisher, LEASE_DURATION_MINUTES
     }, 
     account_manager::{
         account_pool::{
@@ -25,15 +36,26 @@ use super::{
         account::AccountPtr, 
         account_pool_builder::AccountPoolBuilder
     }, 
-    statistics::STATISTICS
+    statistics::STATISTICS,
+    shutdown::Shutdown,
+    controls::{ParserControls, ParserControlsPtr, ParserState},
+    worker::Worker
 };
 
+//running parse tasks are abandoned after this time on shutdown, their tasks are returned to New
+const SHUTDOWN_TIMEOUT_SECONDS: u64 = 30;
+const SETTINGS_RELOAD_INTERVAL_SECONDS: u64 = 10;
+//hung parse is aborted after this time and its task is retried
+const PARSE_TIMEOUT_MINUTES: u64 = 30;
+
 pub type AccountPoolPtr = Arc<AccountPool>;
 pub type ParserThreadCounterPtr = Arc<ParserThreadCounter>;
+//counter is shared by parsing loops of all social networks, so there can be several waiting callers
 pub struct ParserThreadCounter {
     number_concurrent_tasks: AtomicUsize,
     thread_available_notification: Notify,
-    limit: usize
+    idle_notification: Notify,
+    limit: AtomicUsize
 }
 
 impl ParserThreadCounter {
@@ -42,82 +64,221 @@ impl ParserThreadCounter {
         return ParserThreadCounter { 
             number_concurrent_tasks: AtomicUsize::new(0),
             thread_available_notification: Notify::new(),
-            limit
+            idle_notification: Notify::new(),
+            limit: AtomicUsize::new(limit)
         }
     }
 
+    //counter is increased only after a thread is available, so waiting can be cancelled on shutdown
     pub async fn increase(&self) -> usize {
-        let current_number_of_threads = self.number_concurrent_tasks.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
-        STATISTICS.increase_current_running_threads();
-        if current_number_of_threads > 20 {
-            debug!("waiting for thread notification");
-            self.wait_available_thread().await;
+        loop {
+            let current = self.number_concurrent_tasks.load(std::sync::atomic::Ordering::Relaxed);
+            if current >= self.limit.load(std::sync::atomic::Ordering::Relaxed) {
+                debug!("waiting for thread notification");
+                self.wait_available_thread().await;
+                continue;
+            }
+            //other caller could take the thread after the check
+            if self.number_concurrent_tasks.compare_exchange(
+                current, 
+                current + 1, 
+                std::sync::atomic::Ordering::Relaxed, 
+                std::sync::atomic::Ordering::Relaxed
+            ).is_ok() {
+                return current + 1;
+            }
         }
-        return self.number_concurrent_tasks.fetch_add(0, std::sync::atomic::Ordering::Relaxed);
     }
 
     pub async fn decrease(&self) -> usize {
         let current_number_of_threads = self.number_concurrent_tasks.fetch_sub(1, std::sync::atomic::Ordering::Relaxed) - 1;
-        STATISTICS.decrease_current_running_threads();
         self.thread_available_notification.notify_one();
+        if current_number_of_threads == 0 {
+            self.idle_notification.notify_waiters();
+        }
         return current_number_of_threads;
     }
 
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, std::sync::atomic::Ordering::Relaxed);
+        //raised limit can free several threads
+        self.thread_available_notification.notify_waiters();
+        self.thread_available_notification.notify_one();
+    }
+
     async fn wait_available_thread(&self) {
         self.thread_available_notification.notified().await;
     }
 
+    pub async fn wait_idle(&self) {
+        loop {
+            //notification is created before the check, so decrease between them is not missed
+            let idle = self.idle_notification.notified();
+            if self.number_concurrent_tasks.load(std::sync::atomic::Ordering::Relaxed) == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
 
 }
 
 pub struct Parser {
     task_publisher: TaskPublisherPtr,
-    task_receiver: Receiver<ParsingTask>,
+    //taken by parsing loops on start
+    task_receivers: HashMap<SocialNetworkEnum, Receiver<ParsingTask>>,
     account_pool: AccountPoolPtr,
-    thread_counter: ParserThreadCounterPtr
+    thread_counter: ParserThreadCounterPtr,
+    //every social network has its own parsing loop, which waits only for the cap of its network
+    social_network_thread_counters: Arc<HashMap<SocialNetworkEnum, ParserThreadCounterPtr>>,
+    controls: ParserControlsPtr,
+    //used for tasks whose parse panicked or timed out
+    retry_policy: RetryPolicy,
+    shutdown: Shutdown
 }
 
 impl Parser {
 
     pub async fn start(&mut self) {
 
-        self.run_task_publisher();
-
-        loop {
-
-            debug!("starts parsing loop");
-
-            let current_number_of_threads = self.thread_counter.increase().await;
-
-            debug!("current number of parsing threads: {}", current_number_of_threads);
-
-            debug!("receiving task");
-
-            let parsing_task = self.task_receiver
-                .recv()
-                .await;
-            
-            let parsing_task = parsing_task.expect("Task channel closed unexpectedly");
-            
-            debug!("task successfully received");
-            debug!("receiving account");
-
-            let account = self.account_pool
-                .get_account(parsing_task.social_network).await; 
-
-            debug!("account successfully received");
-
-            let thread_counter = self.thread_counter.clone();
+        let task_publisher_handle = self.run_task_publisher();
+        self.run_settings_reload();
+        ParserControls::run_refresh(self.controls.clone());
+        Worker::run_heartbeat(Worker::new(
+            self.task_publisher.get_worker_id().to_string(), 
+            self.account_pool.get_account_ids().await
+        ), self.shutdown.clone());
+
+        let parsing_loops: Vec<JoinHandle<usize>> = std::mem::take(&mut self.task_receivers)
+            .into_iter()
+            .map(|(social_network, task_receiver)| self.run_parsing_loop(social_network, task_receiver))
+            .collect();
+        let mut returned_tasks = 0;
+        for parsing_loop in join_all(parsing_loops).await {
+            returned_tasks += parsing_loop.inspect_err(|e| error!("parsing loop failed: {}", e)).unwrap_or(0);
+        }
 
-            debug!("spawning async parsing task");
+        self.stop(task_publisher_handle, returned_tasks).await;
+    }
 
-            tokio::spawn(async move {
-                STATISTICS.increase_started_parsing_tasks();
-                Self::parse(parsing_task, account).await;
-                thread_counter.decrease().await;
-                STATISTICS.increase_successful_parsing_tasks();
-            });
-        }
+    //returns number of tasks left in the channel of the social network on shutdown
+    fn run_parsing_loop(&self, social_network: SocialNetworkEnum, mut task_receiver: Receiver<ParsingTask>) -> JoinHandle<usize> {
+        let thread_counter = self.thread_counter.clone();
+        let social_network_thread_counter = self.social_network_thread_counters
+            .get(&social_network)
+            .expect("No such social network!")
+            .clone();
+        let task_publisher = self.task_publisher.clone();
+        let account_pool = self.account_pool.clone();
+        let controls = self.controls.clone();
+        let retry_policy = self.retry_policy.clone();
+        let mut shutdown = self.shutdown.clone();
+
+        return tokio::spawn(async move {
+            loop {
+
+                debug!("starts parsing loop of {:?}", social_network);
+                debug!("receiving task");
+
+                let parsing_task = tokio::select! {
+                    biased;
+                    _ = shutdown.wait() => break,
+                    parsing_task = task_receiver.recv() => parsing_task
+                };
+
+                let parsing_task = parsing_task.expect("Task channel closed unexpectedly");
+
+                if controls.get_task_state(&parsing_task) == ParserState::Paused {
+                    debug!("task of paused scope is returned to New");
+                    update_task_with_status(&parsing_task, ParsingTaskStatus::New).await;
+                    task_publisher.finish_task(&parsing_task);
+                    continue;
+                }
+
+                debug!("task successfully received");
+
+                //received task is left in Processing on shutdown, its lease is released on stop
+                tokio::select! {
+                    biased;
+                    _ = shutdown.wait() => break,
+                    _ = social_network_thread_counter.increase() => {}
+                };
+
+                let current_number_of_threads = tokio::select! {
+                    biased;
+                    _ = shutdown.wait() => {
+                        social_network_thread_counter.decrease().await;
+                        break;
+                    },
+                    current_number_of_threads = thread_counter.increase() => current_number_of_threads
+                };
+                STATISTICS.increase_current_running_threads();
+
+                debug!("current number of parsing threads: {}", current_number_of_threads);
+                debug!("receiving account");
+
+                //waiting for an account can take minutes, so it is cancelled on shutdown
+                let account = tokio::select! {
+                    biased;
+                    _ = shutdown.wait() => {
+                        update_task_with_status(&parsing_task, ParsingTaskStatus::New).await;
+                        task_publisher.finish_task(&parsing_task);
+                        social_network_thread_counter.decrease().await;
+                        thread_counter.decrease().await;
+                        STATISTICS.decrease_current_running_threads();
+                        break;
+                    },
+                    account = account_pool.get_account(parsing_task.social_network) => account
+                };
+
+                debug!("account successfully received");
+
+                let thread_counter = thread_counter.clone();
+                let social_network_thread_counter = social_network_thread_counter.clone();
+                let task_publisher = task_publisher.clone();
+                let retry_policy = retry_policy.clone();
+                let worker_id = task_publisher.get_worker_id().to_string();
+
+                debug!("spawning async parsing task");
+
+                tokio::spawn(async move {
+                    STATISTICS.increase_started_parsing_tasks();
+                    start_task_lease(parsing_task._id.unwrap(), &worker_id, account.account_data.get_id(), LEASE_DURATION_MINUTES * 60 * 1000).await;
+                    //parse runs in its own task, so counters are decreased even when it panics
+                    let mut parse = tokio::spawn(Self::parse(parsing_task.clone(), account));
+                    let parsed = match tokio::time::timeout(Duration::from_secs(PARSE_TIMEOUT_MINUTES * 60), &mut parse).await {
+                        Ok(parsed) => parsed.map_err(|e| e.to_string()),
+                        Err(_) => {
+                            parse.abort();
+                            Err(format!("parse did not finish in {} minutes", PARSE_TIMEOUT_MINUTES))
+                        }
+                    };
+                    task_publisher.finish_task(&parsing_task);
+                    social_network_thread_counter.decrease().await;
+                    thread_counter.decrease().await;
+                    STATISTICS.decrease_current_running_threads();
+                    match parsed {
+                        Ok(_) => {
+                            STATISTICS.increase_successful_parsing_tasks();
+                        },
+                        Err(e) => {
+                            error!("parse task of {:?} failed: {}", parsing_task.social_network, e);
+                            STATISTICS.increase_failed_parsing_tasks();
+                            //task is still Processing unless parse updated it before failing
+                            fail_task(&parsing_task, TaskFailure::Transient(e), &retry_policy).await;
+                        }
+                    }
+                });
+            }
+
+            task_receiver.close();
+            let mut returned_tasks = 0;
+            while task_receiver.try_recv().is_ok() {
+                returned_tasks += 1;
+            }
+            return returned_tasks;
+        });
     }
 
     async fn parse(task: ParsingTask, account: AccountPtr) {
@@ -128,10 +289,59 @@ impl Parser {
             .await;
     }
 
-    pub fn run_task_publisher(&self) {
+    //tasks left in the channel and tasks of timed out parse tasks are returned to New
+    async fn stop(&mut self, task_publisher_handle: JoinHandle<()>, returned_tasks: usize) {
+        info!("stopping parser");
+        info!("{} tasks are left in the channel", returned_tasks);
+        task_publisher_handle.await.inspect_err(|e| error!("task publisher failed: {}", e)).ok();
+        if tokio::time::timeout(Duration::from_secs(SHUTDOWN_TIMEOUT_SECONDS), self.thread_counter.wait_idle()).await.is_err() {
+            error!("running parse tasks did not finish in {} seconds", SHUTDOWN_TIMEOUT_SECONDS);
+        }
+        let released = release_worker_leases(self.task_publisher.get_worker_id()).await;
+        remove_worker(self.task_publisher.get_worker_id()).await;
+        info!("parser stopped, {} tasks returned to New", released);
+    }
+
+    fn apply_concurrency_settings(
+        concurrency: &ConcurrencySettings, 
+        thread_counter: &ParserThreadCounter, 
+        social_network_thread_counters: &HashMap<SocialNetworkEnum, ParserThreadCounterPtr>, 
+        task_publisher: &TaskPublisher
+    ) {
+        thread_counter.set_limit(concurrency.max_parse_tasks);
+        for (social_network, counter) in social_network_thread_counters.iter() {
+            counter.set_limit(*concurrency.social_network_limits.get(social_network).unwrap_or(&concurrency.max_parse_tasks));
+        }
+        task_publisher.set_batch_size(concurrency.publisher_batch_size);
+    }
+
+    //concurrency limits are applied when general settings file changes
+    fn run_settings_reload(&self) {
+        let thread_counter = self.thread_counter.clone();
+        let social_network_thread_counters = self.social_network_thread_counters.clone();
+        let task_publisher = self.task_publisher.clone();
+
+        tokio::spawn(async move {
+            let mut applied: Option<ConcurrencySettings> = None;
+            loop {
+                tokio::time::sleep(Duration::from_secs(SETTINGS_RELOAD_INTERVAL_SECONDS)).await;
+                match read_general_settings() {
+                    Ok(general_settings) if applied.as_ref() != Some(&general_settings.concurrency) => {
+                        info!("applying concurrency settings {:?}", general_settings.concurrency);
+                        Self::apply_concurrency_settings(&general_settings.concurrency, &thread_counter, &social_network_thread_counters, &task_publisher);
+                        applied = Some(general_settings.concurrency);
+                    },
+                    Ok(_) => {},
+                    Err(e) => error!("unable to reload general settings: {}", e)
+                }
+            }
+        });
+    }
+
+    pub fn run_task_publisher(&self) -> JoinHandle<()> {
         let task_publisher_ptr_clone = self.task_publisher.clone();
 
-        tokio::spawn( async move {
+        return tokio::spawn( async move {
             task_publisher_ptr_clone.start().await;
         });
     }
@@ -139,28 +349,45 @@ impl Parser {
 
 pub struct ParserBuilder {
     task_publisher_builder: TaskPublisherBuilder,
-    account_pool_builder: AccountPoolBuilder
+    account_pool_builder: AccountPoolBuilder,
+    settings: SettingsPtr,
+    shutdown: Shutdown
 }
 
 impl ParserBuilder {
 
-    pub fn new(task_publisher_builder: TaskPublisherBuilder, account_pool_builder: AccountPoolBuilder) -> ParserBuilder {
+    pub fn new(task_publisher_builder: TaskPublisherBuilder, account_pool_builder: AccountPoolBuilder, settings: SettingsPtr, shutdown: Shutdown) -> ParserBuilder {
         return ParserBuilder { 
             task_publisher_builder: task_publisher_builder, 
-            account_pool_builder: account_pool_builder
+            account_pool_builder: account_pool_builder,
+            settings: settings,
+            shutdown: shutdown
         }
     }
 
-    pub async fn build(self) -> Parser {
+    pub async fn build(self) -> Result<Parser, String> {
+
+        let controls = ParserControls::load().await;
+        let account_pool = self.account_pool_builder.build().await?;
+        let (task_publisher, receivers) = self.task_publisher_builder.build(self.shutdown.clone(), controls.clone(), account_pool.clone()).await;
 
-        let (task_publisher, receiver) = self.task_publisher_builder.build().await;
+        let concurrency = &self.settings.general_settings.concurrency;
+        let thread_counter = ParserThreadCounter::new(concurrency.max_parse_tasks);
+        let social_network_thread_counters: HashMap<SocialNetworkEnum, ParserThreadCounterPtr> = SocialNetworkEnum::iter()
+            .map(|social_network| (social_network, Arc::new(ParserThreadCounter::new(concurrency.max_parse_tasks))))
+            .collect();
+        Parser::apply_concurrency_settings(concurrency, &thread_counter, &social_network_thread_counters, &task_publisher);
         
-        return Parser {
+        return Ok(Parser {
             task_publisher: task_publisher,
-            task_receiver: receiver,
-            account_pool: self.account_pool_builder.build().await,
-            thread_counter: Arc::new(ParserThreadCounter::new(20))
-        }
+            task_receivers: receivers,
+            account_pool: account_pool,
+            thread_counter: Arc::new(thread_counter),
+            social_network_thread_counters: Arc::new(social_network_thread_counters),
+            controls: controls,
+            retry_policy: self.settings.general_settings.retry.clone(),
+            shutdown: self.shutdown
+        });
     }
 
 
@@ -0,0 +1,51 @@
+use log::info;
+use tokio::sync::watch;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+
+//triggered once on ctrl+c or SIGTERM, every loop which should stop holds a clone
+#[derive(Clone)]
+pub struct Shutdown {
+    receiver: watch::Receiver<bool>
+}
+
+impl Shutdown {
+
+    pub fn listen_signals() -> Shutdown {
+        let (sender, receiver) = watch::channel(false);
+        tokio::spawn(async move {
+            Self::wait_signal().await;
+            sender.send(true).ok();
+        });
+        return Shutdown { receiver };
+    }
+
+    #[cfg(unix)]
+    async fn wait_signal() {
+        let mut terminate = signal(SignalKind::terminate()).expect("unable to listen SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => info!("received ctrl+c"),
+            _ = terminate.recv() => info!("received SIGTERM")
+        }
+    }
+
+    //SIGTERM is unix only
+    #[cfg(not(unix))]
+    async fn wait_signal() {
+        tokio::signal::ctrl_c().await.expect("unable to listen ctrl+c");
+        info!("received ctrl+c");
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        return *self.receiver.borrow();
+    }
+
+    pub async fn wait(&mut self) {
+        while !self.is_triggered() {
+            //signal listener is gone, shutdown will never be triggered
+            if self.receiver.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
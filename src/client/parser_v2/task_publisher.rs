@@ -1,108 +1,334 @@
 
-use std::{time::Duration, sync::Arc};
+use std::{
+    time::Duration, 
+    sync::{Arc, Mutex, atomic::{AtomicU64, AtomicUsize, Ordering}}, 
+    collections::{HashMap, HashSet, BTreeMap, hash_map::Entry}, 
+    cmp::Reverse
+};
 
-use futures::StreamExt;
-use log::info;
+use log::{info, error};
+use mongodb::bson::oid::ObjectId;
+use sha2::{Sha256, Digest};
+use strum::IntoEnumIterator;
 use tokio::sync::mpsc::{self, Sender, Receiver};
 
+use super::{
+    shutdown::Shutdown, 
+    fair_scheduler::FairScheduler, 
+    controls::{ParserControlsPtr, ParserState}, 
+    account_manager::account::AccountPoolPtr
+};
+
 use crate::{
     commons::{
         parsing_tasks::{
-            ParsingTaskStatus, 
             ParsingTask
         }, 
-        social_network::SOCIAL_NETWORKS
+        social_network::{SOCIAL_NETWORKS, SocialNetworkEnum},
+        job::Job
     }, 
     client::{
         db::tasks_db::{
-            Limit, 
-            get_tasks_sorted_by_exec_time, update_task_with_status, insert_tasks
+            insert_tasks, create_tasks_indexes, claim_tasks, get_due_task_groups, release_expired_leases, renew_worker_leases, 
+            cancel_job_tasks, assign_tasks_job, TaskGroup
         }, 
+        db::jobs_db::{create_job, get_inactive_job_ids, get_cancelled_job_ids, get_seed_job},
+        db::workers_db::{acquire_leadership, release_leadership, get_leader_marker, set_leader_marker},
+        db::entities_db::create_entities_indexes,
         settings::SettingsPtr
-    }
+    },
+    utils::time::get_timestamp
 };
 
 pub type TaskPublisherPtr = Arc<TaskPublisher>;
 
-#[derive(Clone)]
-pub enum TaskPublisherMod {
-    Recovery,
-    Manual
-}
+//claimed task is returned to New when its worker does not renew the lease in this time
+pub const LEASE_DURATION_MINUTES: u64 = 10;
+const LEASE_REAPER_INTERVAL_SECONDS: u64 = 30;
+//tasks spawned by running tasks of a cancelled job are cancelled during this time
+const CANCELLED_JOB_SWEEP_MINUTES: u64 = 60;
+//seed tasks are inserted by one worker, the leader of this role
+const SEED_LEADER_ROLE: &str = "seed_insertion";
+const LEADERSHIP_LEASE_SECONDS: u64 = 60;
+const LEADERSHIP_RENEW_INTERVAL_SECONDS: u64 = 10;
+//channel is filled with tasks for this time of observed throughput
+const CLAIM_AHEAD_SECONDS: f64 = 10.0;
+//claimed while throughput is not observed yet
+const MIN_CLAIM_SIZE: usize = 10;
+const THROUGHPUT_SMOOTHING: f64 = 0.3;
+const MIN_FETCH_INTERVAL_MILLIS: u64 = 1000;
+const MAX_FETCH_INTERVAL_MILLIS: u64 = 10000;
 
 pub struct TaskPublisher {
-    limit: u64,
-    sender: Sender<ParsingTask>,
-    startup_mod: TaskPublisherMod,
-    settings: SettingsPtr
+    //max number of tasks claimed at once, channel capacity is the limit set on start
+    limit: AtomicU64,
+    //every social network has its own channel, so a network at its cap
+    //does not hold back tasks of other networks
+    senders: HashMap<SocialNetworkEnum, Sender<ParsingTask>>,
+    channel_capacity: usize,
+    settings: SettingsPtr,
+    //owner of the leases of claimed tasks
+    worker_id: String,
+    shutdown: Shutdown,
+    controls: ParserControlsPtr,
+    account_pool: AccountPoolPtr,
+    //tasks sent to the channel and not finished by parser yet, by social network
+    unfinished: HashMap<SocialNetworkEnum, AtomicUsize>,
+    //ids of queued and running tasks, only their leases are renewed
+    in_flight: Arc<Mutex<HashSet<ObjectId>>>
 }
 
 impl TaskPublisher {
 
-    pub fn new(task_publisher_mod: TaskPublisherMod, settings: SettingsPtr, limit: u64) -> (TaskPublisherPtr, Receiver<ParsingTask>)  {
-        let (sd, rc) = mpsc::channel(limit as usize);
+    pub fn new(
+        settings: SettingsPtr, 
+        limit: u64, 
+        shutdown: Shutdown, 
+        controls: ParserControlsPtr, 
+        account_pool: AccountPoolPtr
+    ) -> (TaskPublisherPtr, HashMap<SocialNetworkEnum, Receiver<ParsingTask>>)  {
+        let mut senders = HashMap::new();
+        let mut receivers = HashMap::new();
+        for social_network in SocialNetworkEnum::iter() {
+            let (sd, rc) = mpsc::channel(limit as usize);
+            senders.insert(social_network, sd);
+            receivers.insert(social_network, rc);
+        }
         return (
             Arc::new(TaskPublisher { 
-                limit: limit, 
-                sender: sd,
-                startup_mod: task_publisher_mod,
-                settings: settings
+                limit: AtomicU64::new(limit), 
+                senders,
+                channel_capacity: limit as usize,
+                worker_id: Self::get_new_worker_id(&settings),
+                settings: settings,
+                shutdown,
+                controls,
+                account_pool,
+                unfinished: SocialNetworkEnum::iter().map(|social_network| (social_network, AtomicUsize::new(0))).collect(),
+                in_flight: Arc::new(Mutex::new(HashSet::new()))
             }),
-            rc
+            receivers
         )
     }
 
     pub async fn start(&self) {
-        match self.startup_mod {
-            TaskPublisherMod::Recovery => self.push_tasks(
-                self.fetch_recovery().await
-            ).await,
-            TaskPublisherMod::Manual => self.read_settings_tasks(self.settings.clone()).await
+        create_tasks_indexes().await;
+        create_entities_indexes().await;
+        //tasks left in Processing by a stopped worker are returned by the lease reaper
+        Self::run_lease_reaper(self.worker_id.clone(), self.in_flight.clone());
+
+        let mut shutdown = self.shutdown.clone();
+        let mut leader = false;
+        let mut leadership_renew_time = 0;
+        let mut throughput = Throughput::new();
+        while !shutdown.is_triggered() {
+            if get_timestamp() >= leadership_renew_time {
+                leader = self.update_leadership(leader).await;
+                leadership_renew_time = get_timestamp() + LEADERSHIP_RENEW_INTERVAL_SECONDS * 1000;
+            }
+            let queued = self.get_queued();
+            throughput.update(queued);
+            let tasks = self.fetch(self.get_claim_size(&throughput, queued)).await;
+            throughput.pushed(tasks.len());
+            if !self.push_tasks(tasks).await {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Self::get_fetch_interval(&throughput, self.get_queued())) => {},
+                _ = shutdown.wait() => {}
+            }
         }
+        if leader {
+            release_leadership(SEED_LEADER_ROLE, &self.worker_id).await;
+        }
+        info!("task publisher stopped");
+    }
 
-        loop {
-            self.push_tasks(
-                self.fetch().await
-            ).await;
-            tokio::time::sleep(Duration::from_secs(1)).await;   
+    //leadership is kept when it can't be renewed because of db error
+    async fn update_leadership(&self, leader: bool) -> bool {
+        let is_leader = match acquire_leadership(SEED_LEADER_ROLE, &self.worker_id, LEADERSHIP_LEASE_SECONDS * 1000).await {
+            Ok(is_leader) => is_leader,
+            Err(e) => {
+                error!("unable to renew leadership of {}: {}", SEED_LEADER_ROLE, e);
+                return leader;
+            }
+        };
+        if is_leader && !leader {
+            info!("worker {} became the leader of {}", self.worker_id, SEED_LEADER_ROLE);
+            self.read_settings_tasks(self.settings.clone()).await;
+        } else if !is_leader && leader {
+            info!("worker {} lost the leadership of {}", self.worker_id, SEED_LEADER_ROLE);
         }
+        return is_leader;
     }
 
-    async fn push_tasks(&self, tasks: Vec<ParsingTask>) {
-        tokio_stream::iter(
-            tasks
-        )
-        .for_each_concurrent(8, |item| async {
-            update_task_with_status(item._id.unwrap(), ParsingTaskStatus::Processing).await;
-            self.sender.send(item).await.expect("error while sending parsing task");
-        })
-        .await;
+    //channel is not resized, claimed tasks are also limited by free slots of the channel
+    pub fn set_batch_size(&self, limit: u64) {
+        self.limit.store(limit, Ordering::Relaxed);
     }
 
-    async fn fetch(&self) -> Vec<ParsingTask> {
-        self.fetch_tasks(
-            vec![
-                ParsingTaskStatus::New
-            ]
-        ).await
+    fn get_new_worker_id(settings: &SettingsPtr) -> String {
+        let suffix = ObjectId::new().to_hex();
+        return match &settings.general_settings.worker_name {
+            Some(name) => format!("{}-{}", name, suffix),
+            None => suffix
+        };
     }
 
-    async fn fetch_recovery(&self) -> Vec<ParsingTask> {
-        self.fetch_tasks(
-            vec![
-                ParsingTaskStatus::Processing
-            ]
-        ).await
+    pub fn get_worker_id(&self) -> &str {
+        return &self.worker_id;
     }
 
-    async fn fetch_tasks(&self, statuses: Vec<ParsingTaskStatus>) -> Vec<ParsingTask> {
-        get_tasks_sorted_by_exec_time(
-                statuses,
-                Limit::Limit(self.limit)
-        ).await
+    //number of tasks in the channels not received by parser yet
+    fn get_queued(&self) -> usize {
+        return self.senders
+            .values()
+            .map(|sender| self.channel_capacity.saturating_sub(sender.capacity()))
+            .sum();
     }
 
-    async fn read_settings_tasks(&self, settings: SettingsPtr) {
+    fn get_free_slots(&self) -> usize {
+        return self.senders.values().map(|sender| sender.capacity()).sum();
+    }
+
+    //channel is filled up to CLAIM_AHEAD_SECONDS of throughput, so claimed tasks run soon
+    fn get_claim_size(&self, throughput: &Throughput, queued: usize) -> usize {
+        let wanted = ((throughput.rate * CLAIM_AHEAD_SECONDS) as usize).max(MIN_CLAIM_SIZE);
+        return wanted
+            .saturating_sub(queued)
+            .min(self.get_free_slots())
+            .min(self.limit.load(Ordering::Relaxed) as usize);
+    }
+
+    //next fetch is when about half of the queued tasks are taken
+    fn get_fetch_interval(throughput: &Throughput, queued: usize) -> Duration {
+        if throughput.rate <= 0.0 {
+            return Duration::from_millis(MIN_FETCH_INTERVAL_MILLIS);
+        }
+        let millis = (queued as f64 * 1000.0 / throughput.rate / 2.0) as u64;
+        return Duration::from_millis(millis.clamp(MIN_FETCH_INTERVAL_MILLIS, MAX_FETCH_INTERVAL_MILLIS));
+    }
+
+    //called by parser when the task is parsed or returned
+    pub fn finish_task(&self, task: &ParsingTask) {
+        self.unfinished[&task.social_network].fetch_sub(1, Ordering::Relaxed);
+        self.in_flight.lock().unwrap().remove(&task._id.unwrap());
+    }
+
+    //returns false when parser closed the channel, not sent tasks are released by parser
+    async fn push_tasks(&self, tasks: Vec<ParsingTask>) -> bool {
+        for item in tasks.into_iter() {
+            self.unfinished[&item.social_network].fetch_add(1, Ordering::Relaxed);
+            self.in_flight.lock().unwrap().insert(item._id.unwrap());
+            if self.senders[&item.social_network].send(item).await.is_err() {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    //tasks of higher priority are claimed first. tasks of the same priority are claimed
+    //by weighted round robin over social networks and seeds, so a big backlog of one seed
+    //does not delay tasks of the others
+    async fn fetch(&self, limit: usize) -> Vec<ParsingTask> {
+        if limit == 0 {
+            return Vec::new();
+        }
+        let inactive_jobs = get_inactive_job_ids(get_timestamp().saturating_sub(CANCELLED_JOB_SWEEP_MINUTES * 60 * 1000)).await;
+        let mut groups = get_due_task_groups(&inactive_jobs).await;
+        //paused and draining networks and seeds are not claimed
+        groups.retain(|group| self.controls.get_group_state(group) == ParserState::Running);
+        let mut budgets = self.get_request_budgets(&groups).await;
+        let mut tiers: BTreeMap<Reverse<i64>, Vec<TaskGroup>> = BTreeMap::new();
+        for group in groups.into_iter() {
+            tiers.entry(Reverse(group.priority)).or_default().push(group);
+        }
+        let mut tasks = Vec::new();
+        for groups in tiers.into_values() {
+            if tasks.len() >= limit {
+                break;
+            }
+            let scheduler = FairScheduler::new(groups, &self.settings.general_settings.scheduling);
+            self.claim_tier(scheduler, &inactive_jobs, &mut budgets, limit, &mut tasks).await;
+        }
+        return tasks;
+    }
+
+    //free slots are shared by the scheduler, then every group is claimed at once.
+    //groups which had less due tasks than their share are removed and slots are shared again
+    async fn claim_tier(
+        &self, 
+        mut scheduler: FairScheduler, 
+        inactive_jobs: &[ObjectId], 
+        budgets: &mut HashMap<SocialNetworkEnum, usize>, 
+        limit: usize, 
+        tasks: &mut Vec<ParsingTask>
+    ) {
+        while tasks.len() < limit {
+            let mut shares: HashMap<TaskGroup, usize> = HashMap::new();
+            for _ in tasks.len()..limit {
+                match scheduler.next() {
+                    Some(group) => *shares.entry(group).or_default() += 1,
+                    None => break
+                }
+            }
+            if shares.is_empty() {
+                break;
+            }
+            for (group, share) in shares.into_iter() {
+                let budget = budgets.get_mut(&group.social_network).unwrap();
+                let share = share.min(*budget);
+                let claimed = if share > 0 {
+                    claim_tasks(&self.worker_id, LEASE_DURATION_MINUTES * 60 * 1000, inactive_jobs, &group, share).await
+                } else {
+                    Vec::new()
+                };
+                if claimed.len() < share || share == 0 {
+                    scheduler.remove(&group);
+                }
+                *budget -= claimed.len();
+                tasks.extend(claimed);
+            }
+        }
+    }
+
+    //request budget of accounts by social network without queued and running tasks of the network,
+    //capped by free slots of the network channel, so sending claimed tasks does not wait.
+    //running task is subtracted even when its account limit is already decreased,
+    //so budget is underestimated by at most the number of running tasks
+    async fn get_request_budgets(&self, groups: &[TaskGroup]) -> HashMap<SocialNetworkEnum, usize> {
+        let mut budgets = HashMap::new();
+        for group in groups.iter() {
+            if let Entry::Vacant(entry) = budgets.entry(group.social_network) {
+                let budget = self.account_pool.get_request_budget(group.social_network).await;
+                let unfinished = self.unfinished[&group.social_network].load(Ordering::Relaxed);
+                let free_slots = self.senders[&group.social_network].capacity();
+                entry.insert(budget.saturating_sub(unfinished).min(free_slots));
+            }
+        }
+        return budgets;
+    }
+
+    //own leases are renewed before expired leases of stopped workers are released.
+    //lease of a task which was not finished by parser expires as the task is not in flight
+    fn run_lease_reaper(worker_id: String, in_flight: Arc<Mutex<HashSet<ObjectId>>>) {
+        tokio::spawn(async move {
+            loop {
+                let ids: Vec<ObjectId> = in_flight.lock().unwrap().iter().cloned().collect();
+                renew_worker_leases(&worker_id, &ids, LEASE_DURATION_MINUTES * 60 * 1000).await;
+                for job in get_cancelled_job_ids(get_timestamp().saturating_sub(CANCELLED_JOB_SWEEP_MINUTES * 60 * 1000)).await {
+                    cancel_job_tasks(job).await;
+                }
+                let released = release_expired_leases().await;
+                if released > 0 {
+                    info!("released {} parsing tasks with expired lease", released);
+                }
+                tokio::time::sleep(Duration::from_secs(LEASE_REAPER_INTERVAL_SECONDS)).await;
+            }
+        });
+    }
+
+    fn prepare_settings_tasks(settings: SettingsPtr) -> Vec<ParsingTask> {
         let mut parsing_tasks: Vec<ParsingTask> = Vec::new();
 
         for social_network_settings in settings.social_network_settings.values() {
@@ -118,30 +344,138 @@ impl TaskPublisher {
             parsing_tasks.append(&mut tasks);
 
         }
+        return parsing_tasks;
+    }
+
+    async fn read_settings_tasks(&self, settings: SettingsPtr) {
+        let mut parsing_tasks = Self::prepare_settings_tasks(settings);
 
+        //seeds are inserted once per settings, marker keeps the inserted version
+        let marker = Self::get_seed_marker(&parsing_tasks);
+        if get_leader_marker(SEED_LEADER_ROLE).await.as_ref() == Some(&marker) {
+            info!("seed tasks of these settings are already inserted");
+            return;
+        }
+        Self::create_seed_jobs(&mut parsing_tasks).await;
         insert_tasks(&parsing_tasks).await;
+        if !set_leader_marker(SEED_LEADER_ROLE, &self.worker_id, &marker).await {
+            error!("leadership of {} was lost while inserting seed tasks", SEED_LEADER_ROLE);
+        }
+    }
+
+    //digest is stable across builds, so workers of different versions agree on the marker
+    fn get_seed_marker(parsing_tasks: &[ParsingTask]) -> String {
+        let mut keys: Vec<String> = parsing_tasks.iter().map(|task| task.get_key()).collect();
+        keys.sort();
+        let mut hasher = Sha256::new();
+        for key in keys.iter() {
+            hasher.update(key.as_bytes());
+            hasher.update(b"\n");
+        }
+        return format!("{:x}", hasher.finalize());
+    }
+
+    //crawl of the seed from settings under a new job, e.g. "backfill r/bitcoin top all-time".
+    //New tasks of the seed are moved to the job. returns None when there is no such seed
+    pub async fn create_named_job(settings: SettingsPtr, social_network: SocialNetworkEnum, seed: &str, name: String) -> Option<ObjectId> {
+        let mut parsing_tasks: Vec<ParsingTask> = Self::prepare_settings_tasks(settings)
+            .into_iter()
+            .filter(|task| task.social_network == social_network && task.seed.as_ref().is_some_and(|task_seed| task_seed.name == seed))
+            .collect();
+        if parsing_tasks.is_empty() {
+            return None;
+        }
+        let job = create_job(&Job::new(name, social_network, seed.to_string())).await;
+        for task in parsing_tasks.iter_mut() {
+            task.job = Some(job);
+        }
+        insert_tasks(&parsing_tasks).await;
+        assign_tasks_job(parsing_tasks.iter().map(|task| task.get_key()).collect(), job).await;
+        return Some(job);
+    }
+
+    //seed tasks join the job of the seed, job named as the seed is created when there is none
+    async fn create_seed_jobs(parsing_tasks: &mut Vec<ParsingTask>) {
+        let mut jobs: HashMap<(SocialNetworkEnum, String), ObjectId> = HashMap::new();
+        for task in parsing_tasks.iter_mut() {
+            let seed = match &task.seed {
+                Some(seed) => seed.name.clone(),
+                None => continue
+            };
+            let job = match jobs.get(&(task.social_network, seed.clone())) {
+                Some(job) => *job,
+                None => {
+                    let job = match get_seed_job(task.social_network, &seed).await {
+                        Some(job) => job,
+                        None => {
+                            let job = create_job(&Job::new(seed.clone(), task.social_network, seed.clone())).await;
+                            info!("created job {} for seed {}", job, seed);
+                            job
+                        }
+                    };
+                    jobs.insert((task.social_network, seed), job);
+                    job
+                }
+            };
+            task.job = Some(job);
+        }
     }
 
 }
 
 pub struct TaskPublisherBuilder {
     limit: u64,
-    startup_mod: TaskPublisherMod,
     settings: SettingsPtr
 }
 
 impl TaskPublisherBuilder {
 
-    pub fn new(startup_mode: TaskPublisherMod, settings: SettingsPtr, limit: u64) -> TaskPublisherBuilder {
+    pub fn new(settings: SettingsPtr, limit: u64) -> TaskPublisherBuilder {
         return TaskPublisherBuilder { 
-            startup_mod: startup_mode, 
             settings: settings, 
             limit: limit 
         };
     }
 
-    pub async fn build(self) -> (TaskPublisherPtr, Receiver<ParsingTask>) {
-        return TaskPublisher::new(self.startup_mod, self.settings, self.limit);
+    pub async fn build(
+        self, 
+        shutdown: Shutdown, 
+        controls: ParserControlsPtr, 
+        account_pool: AccountPoolPtr
+    ) -> (TaskPublisherPtr, HashMap<SocialNetworkEnum, Receiver<ParsingTask>>) {
+        return TaskPublisher::new(self.settings, self.limit, shutdown, controls, account_pool);
+    }
+
+}
+
+//tasks taken from the channel per second, exponential moving average
+struct Throughput {
+    rate: f64,
+    queued: usize,
+    update_time: u64
+}
+
+impl Throughput {
+
+    fn new() -> Throughput {
+        return Throughput { 
+            rate: 0.0, 
+            queued: 0, 
+            update_time: get_timestamp() 
+        };
+    }
+
+    //queued is the number of tasks in the channel now
+    fn update(&mut self, queued: usize) {
+        let now = get_timestamp();
+        let elapsed_seconds = now.saturating_sub(self.update_time).max(1) as f64 / 1000.0;
+        let taken = self.queued.saturating_sub(queued) as f64;
+        self.rate = self.rate * (1.0 - THROUGHPUT_SMOOTHING) + taken / elapsed_seconds * THROUGHPUT_SMOOTHING;
+        self.queued = queued;
+        self.update_time = now;
     }
 
+    fn pushed(&mut self, count: usize) {
+        self.queued += count;
+    }
 }
\ No newline at end of file
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use log::{info, warn};
+use mongodb::bson::DateTime;
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    client::db::{
+        client::{DBCollection, DATABASE_COLLECTIONS}, 
+        workers_db::{update_worker, get_workers}
+    }, 
+    utils::time::get_timestamp
+};
+
+use super::shutdown::Shutdown;
+
+const HEARTBEAT_INTERVAL_SECONDS: u64 = 10;
+//worker without heartbeat in this time is considered stopped
+pub const WORKER_TIMEOUT_SECONDS: u64 = 60;
+
+//worker process sharing the task store with other workers
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Worker {
+    //worker id, owner of the leases of claimed tasks
+    pub _id: String,
+    pub start_time: DateTime,
+    pub heartbeat_time: DateTime,
+    //accounts of the worker account pool
+    pub accounts: Vec<String>
+}
+
+impl Worker {
+
+    pub fn new(id: String, accounts: Vec<String>) -> Worker {
+        let now = DateTime::from_millis(get_timestamp() as i64);
+        return Worker { 
+            _id: id, 
+            start_time: now, 
+            heartbeat_time: now, 
+            accounts 
+        };
+    }
+
+    pub fn is_alive(&self) -> bool {
+        return self.heartbeat_time.timestamp_millis() as u64 + WORKER_TIMEOUT_SECONDS * 1000 > get_timestamp();
+    }
+
+    //heartbeat stops on shutdown, stopped parser removes the worker record
+    pub fn run_heartbeat(mut worker: Worker, mut shutdown: Shutdown) {
+        tokio::spawn(async move {
+            Self::warn_shared_accounts(&worker).await;
+            while !shutdown.is_triggered() {
+                worker.heartbeat_time = DateTime::from_millis(get_timestamp() as i64);
+                update_worker(&worker).await;
+                tokio::select! {
+                    _ = tokio::time::sleep(Duration::from_secs(HEARTBEAT_INTERVAL_SECONDS)) => {},
+                    _ = shutdown.wait() => {}
+                }
+            }
+        });
+    }
+
+    //request limits of an account are not shared between workers
+    async fn warn_shared_accounts(worker: &Worker) {
+        for other in get_workers().await.iter().filter(|other| other._id != worker._id && other.is_alive()) {
+            for account in worker.accounts.iter().filter(|account| other.accounts.contains(account)) {
+                warn!("account {} is also used by worker {}", account, other._id);
+            }
+        }
+        info!("worker {} started with {} accounts", worker._id, worker.accounts.len());
+    }
+}
+
+impl DBCollection for Worker {
+    fn get_collection() -> String {
+        return DATABASE_COLLECTIONS::WORKERS.to_string();
+    }
+}
+
+//role held by one worker until the lease expires
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LeaderLease {
+    pub _id: String,
+    pub holder: String,
+    pub expires_at: u64,
+    //state the leaders of the role have already acted on, kept across leaders
+    #[serde(default)]
+    pub marker: Option<String>
+}
+
+impl DBCollection for LeaderLease {
+    fn get_collection() -> String {
+        return DATABASE_COLLECTIONS::LEADERS.to_string();
+    }
+}
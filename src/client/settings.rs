@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fs::{self, File}, io::BufReader, sync::Arc};
+use std::{collections::HashMap, error::Error, fs::{self, File}, io::BufReader, sync::Arc, path::{Path, PathBuf}};
 
 use derivative::Derivative;
 use log::info;
@@ -6,7 +6,7 @@ use serde::{Serialize, Deserialize};
 use serde_json::Value;
 use strum::IntoEnumIterator;
 
-use crate::commons::social_network::SocialNetworkEnum;
+use crate::commons::{social_network::SocialNetworkEnum, parsing_tasks::RetryPolicy};
 
 pub type SettingsPtr = Arc<Settings>;
 
@@ -19,6 +19,15 @@ pub struct Account {
     pub social_network: SocialNetworkEnum
 }
 
+impl Account {
+    //login or public key identifies the account in worker records and task leases
+    pub fn get_id(&self) -> String {
+        return self.login.clone()
+            .or_else(|| self.public_key.clone())
+            .unwrap_or_default();
+    }
+}
+
 #[derive(Serialize, Deserialize, Hash, PartialEq, Eq, Debug, Clone)]
 pub struct Proxy {
     pub host: String, 
@@ -52,10 +61,80 @@ pub struct Settings {
     pub social_network_settings: HashMap<SocialNetworkEnum, SocialNetworkSettings>
 }
 
-#[derive(Serialize, Deserialize, Hash, PartialEq, Eq, Debug)]
+#[derive(Derivative, Serialize, Deserialize)]
+#[derivative(PartialEq, Debug, Hash, Eq)]
 pub struct GeneralSettings {
     pub proxies: Vec<Proxy>,
-    pub disable_proxy: bool
+    pub disable_proxy: bool,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    #[derivative(Hash="ignore")]
+    pub concurrency: ConcurrencySettings,
+    #[serde(default)]
+    #[derivative(Hash="ignore")]
+    pub scheduling: SchedulingSettings,
+    //name of the worker in the shared task store, random suffix is added on every start,
+    //so processes started with the same settings file never share an id
+    #[serde(default)]
+    #[derivative(Hash="ignore")]
+    pub worker_name: Option<String>,
+    #[serde(default)]
+    #[derivative(Hash="ignore")]
+    pub proxy_assignment: ProxyAssignment,
+    //proxy pools by social network, accounts of a network without pool use proxies
+    #[serde(default)]
+    #[derivative(Hash="ignore")]
+    pub social_network_proxies: HashMap<SocialNetworkEnum, Vec<Proxy>>
+}
+
+//how proxies of a pool are given to accounts of a social network, in order of accounts in settings
+#[derive(Serialize, Deserialize, PartialEq, Eq, Hash, Debug, Clone, Copy, Default)]
+pub enum ProxyAssignment {
+    //every account gets its own proxy, start fails when there are less proxies than accounts
+    Sticky,
+    //proxies are given in turn, accounts share proxies when there are less proxies than accounts
+    #[default]
+    RoundRobin
+}
+
+//share of claimed tasks of a network or a seed is proportional to its weight, default weight is 1
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SchedulingSettings {
+    pub social_network_weights: HashMap<SocialNetworkEnum, u64>,
+    //weights by seed name, e.g. "r/bitcoin"
+    pub seed_weights: HashMap<String, u64>
+}
+
+//limits are reloaded from general_settings.json while parser is running,
+//except worker_threads and auth_concurrency which are applied on start
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[serde(default)]
+pub struct ConcurrencySettings {
+    pub worker_threads: usize,
+    //parse tasks running at once
+    pub max_parse_tasks: usize,
+    //caps of parse tasks by social network within max_parse_tasks
+    pub social_network_limits: HashMap<SocialNetworkEnum, usize>,
+    //max tasks claimed by publisher at once, claim size also follows free channel
+    //slots, request budget of accounts and throughput. channel capacity is the batch size
+    //set on start, so a bigger batch size applied on reload is capped by it until restart
+    pub publisher_batch_size: u64,
+    //accounts authorized at once
+    pub auth_concurrency: usize
+}
+
+impl Default for ConcurrencySettings {
+    fn default() -> Self {
+        Self { 
+            worker_threads: 50, 
+            max_parse_tasks: 20, 
+            social_network_limits: HashMap::new(), 
+            publisher_batch_size: 1000, 
+            auth_concurrency: 8 
+        }
+    }
 }
 
 #[derive(Derivative, Serialize, Deserialize)]
@@ -71,7 +150,7 @@ pub struct SocialNetworkSettings {
 
 
 enum SettingsPathType {
-    GeneralSettingsPath(String),
+    GeneralSettingsPath(),
     SocialNetworkSettingsPath(SocialNetworkEnum, String),
     OtherPath()
 }
@@ -86,8 +165,8 @@ impl SettingsPathType {
                 return SettingsPathType::SocialNetworkSettingsPath(social_net, path);
             }
         }
-        if path.contains("general_settings.json") {
-            return SettingsPathType::GeneralSettingsPath(path);
+        if path.contains(GENERAL_SETTINGS_FILE) {
+            return SettingsPathType::GeneralSettingsPath();
         } else {
             return SettingsPathType::OtherPath();
         }
@@ -95,10 +174,23 @@ impl SettingsPathType {
 
 }
 
+const SETTINGS_DIR: &str = "./settings";
+const GENERAL_SETTINGS_FILE: &str = "general_settings.json";
+
+fn get_general_settings_path() -> PathBuf {
+    return Path::new(SETTINGS_DIR).join(GENERAL_SETTINGS_FILE);
+}
+
+//used on start and on reload of concurrency settings
+pub fn read_general_settings() -> Result<GeneralSettings, Box<dyn Error + Send + Sync>> {
+    let reader = BufReader::new(File::open(get_general_settings_path())?);
+    return Ok(serde_json::from_reader(reader)?);
+}
+
 pub fn get_settings() -> SettingsPtr {
 
     info!("parsing settings files");
-    let paths = fs::read_dir("./settings").expect("not found settings dir");
+    let paths = fs::read_dir(SETTINGS_DIR).expect("not found settings dir");
 
     let mut general_settings: Option<GeneralSettings> = None;
 
@@ -108,10 +200,8 @@ pub fn get_settings() -> SettingsPtr {
 
         match SettingsPathType::test(path.unwrap().path().to_str().unwrap().to_string()) {
 
-            SettingsPathType::GeneralSettingsPath(general_settings_path) => {
-                let f = File::open(general_settings_path).expect("unable to read general_settings file");
-                let reader = BufReader::new(f);
-                general_settings = Some(serde_json::from_reader(reader).expect("unable to parse general_settings file"));
+            SettingsPathType::GeneralSettingsPath() => {
+                general_settings = Some(read_general_settings().expect("unable to read general_settings file"));
             },
 
             SettingsPathType::SocialNetworkSettingsPath(social_net, social_network_settings_folder) => {
@@ -0,0 +1,76 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Serialize, Deserialize};
+use strum::EnumIter;
+
+use crate::client::db::client::{DBCollection, DATABASE_COLLECTIONS};
+
+use crate::utils::time::get_timestamp;
+
+use super::social_network::SocialNetworkEnum;
+
+//one document per fetch, history of community is the set of its snapshots
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CommunitySnapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    //social network id
+    pub id: String,
+    pub name: String,
+    pub social_network: SocialNetworkEnum,
+    pub snapshot_time: DateTime,
+    pub creation_date: DateTime,
+
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub subscribers: Option<u64>,
+    pub active_users: Option<u64>,
+    pub nsfw: Option<bool>
+}
+
+impl DBCollection for CommunitySnapshot {
+    fn get_collection() -> String {
+        return DATABASE_COLLECTIONS::COMMUNITIES.to_string();
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug, EnumIter, strum::Display)]
+pub enum DiscoveryStatus {
+    //waiting for metadata to check subscribers threshold
+    Pending,
+    Active,
+    Rejected
+}
+
+//community found by crawler, stored once per social network
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DiscoveredCommunity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub name: String,
+    pub social_network: SocialNetworkEnum,
+    //seed which led to the community and number of hops from it
+    pub seed: String,
+    pub depth: u64,
+    pub status: DiscoveryStatus,
+    pub discovery_time: DateTime
+}
+
+impl DiscoveredCommunity {
+    pub fn new(name: String, social_network: SocialNetworkEnum, seed: String, depth: u64) -> DiscoveredCommunity {
+        return DiscoveredCommunity { 
+            _id: None, 
+            name, 
+            social_network, 
+            seed, 
+            depth, 
+            status: DiscoveryStatus::Pending, 
+            discovery_time: DateTime::from_millis(get_timestamp() as i64) 
+        };
+    }
+}
+
+impl DBCollection for DiscoveredCommunity {
+    fn get_collection() -> String {
+        return DATABASE_COLLECTIONS::DISCOVERED_COMMUNITIES.to_string();
+    }
+}
\ No newline at end of file
@@ -0,0 +1,40 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Serialize, Deserialize};
+
+use crate::{client::db::client::{DBCollection, DATABASE_COLLECTIONS}, utils::time::get_timestamp};
+
+use super::{social_network::SocialNetworkEnum, entity::Entity};
+
+//engagement of entity at the moment of fetch
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EngagementSnapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub entity_id: String,
+    pub social_network: SocialNetworkEnum,
+    pub snapshot_time: DateTime,
+    pub rating: Option<i64>,
+    pub comments_count: Option<u64>,
+    pub upvote_ratio: Option<f64>
+}
+
+impl EngagementSnapshot {
+    //comments count and upvote ratio are read by the social network module from entity extension
+    pub fn new(entity: &Entity, comments_count: Option<u64>, upvote_ratio: Option<f64>) -> EngagementSnapshot {
+        return EngagementSnapshot { 
+            _id: None, 
+            entity_id: entity.id.clone(), 
+            social_network: entity.social_network, 
+            snapshot_time: DateTime::from_millis(get_timestamp() as i64), 
+            rating: entity.rating, 
+            comments_count, 
+            upvote_ratio 
+        };
+    }
+}
+
+impl DBCollection for EngagementSnapshot {
+    fn get_collection() -> String {
+        return DATABASE_COLLECTIONS::ENGAGEMENT.to_string();
+    }
+}
\ No newline at end of file
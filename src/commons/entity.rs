@@ -1,7 +1,9 @@
-use mongodb::bson::{DateTime, oid::ObjectId};
+use mongodb::bson::{DateTime, oid::ObjectId, Document};
 use serde::{Serialize, Deserialize};
 
-use crate::{client::db::client::{DBCollection, DATABASE_COLLECTIONS}};
+use crate::client::db::client::{DBCollection, DATABASE_COLLECTIONS};
+
+use crate::utils::time::get_timestamp;
 
 use super::social_network::SocialNetworkEnum;
 
@@ -9,7 +11,18 @@ use super::social_network::SocialNetworkEnum;
 pub enum EntityType {
     Post,
     Comment,
-    Message
+    Message,
+    User
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub enum ContentStatus {
+    #[default]
+    Active,
+    //deleted by author
+    Deleted,
+    //removed by moderators
+    Removed
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,11 +45,76 @@ pub struct Entity {
 
     pub rating: Option<i64>, 
 
-    pub images: Vec<String>
+    pub images: Vec<String>,
+
+    #[serde(default)]
+    pub content_status: ContentStatus,
+
+    //thread structure, set for comments only
+    //parent entity id, top level comments point to the root post
+    pub parent_id: Option<String>,
+    pub root_id: Option<String>,
+    pub depth: Option<u64>,
+
+    //social network specific fields, their layout is defined by the social network module
+    pub extension: Option<Document>
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntityThread {
+    pub entity: Entity,
+    pub replies: Vec<EntityThread>
+}
+
+impl Entity {
+    pub fn has_same_content(&self, other: &Entity) -> bool {
+        return self.title == other.title 
+            && self.content == other.content 
+            && self.author_name == other.author_name 
+            && self.content_status == other.content_status;
+    }
 }
 
 impl DBCollection for Entity {
     fn get_collection() -> String {
         return DATABASE_COLLECTIONS::ENTITIES.to_string();
     }
+}
+
+//content of entity before it was replaced by newer revision
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EntityVersion {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub entity_id: String,
+    pub social_network: SocialNetworkEnum,
+    //time when the change was detected
+    pub replace_time: DateTime,
+    pub title: Option<String>,
+    pub content: Option<String>,
+    pub author_name: Option<String>,
+    pub content_status: ContentStatus,
+    pub replaced_by_status: ContentStatus
+}
+
+impl EntityVersion {
+    pub fn new(previous: Entity, current: &Entity) -> EntityVersion {
+        return EntityVersion { 
+            _id: None, 
+            entity_id: previous.id, 
+            social_network: previous.social_network, 
+            replace_time: DateTime::from_millis(get_timestamp() as i64), 
+            title: previous.title, 
+            content: previous.content, 
+            author_name: previous.author_name, 
+            content_status: previous.content_status, 
+            replaced_by_status: current.content_status.clone() 
+        };
+    }
+}
+
+impl DBCollection for EntityVersion {
+    fn get_collection() -> String {
+        return DATABASE_COLLECTIONS::ENTITY_VERSIONS.to_string();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,63 @@
+use mongodb::bson::{DateTime, oid::ObjectId};
+use serde::{Serialize, Deserialize};
+use strum::EnumIter;
+
+use crate::client::db::client::{DBCollection, DATABASE_COLLECTIONS};
+
+use crate::utils::time::get_timestamp;
+
+use super::social_network::SocialNetworkEnum;
+
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug, EnumIter, strum::Display)]
+pub enum JobStatus {
+    Active,
+    //tasks of the job are not claimed until it is resumed
+    Paused,
+    Cancelled
+}
+
+//named crawl started from a seed, every task spawned from the seed tasks carries its id
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Job {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub _id: Option<ObjectId>,
+    pub name: String,
+    pub social_network: SocialNetworkEnum,
+    pub seed: String,
+    pub status: JobStatus,
+    pub creation_time: DateTime,
+    //time of the last status change
+    #[serde(default)]
+    pub update_time: Option<DateTime>
+}
+
+impl Job {
+    pub fn new(name: String, social_network: SocialNetworkEnum, seed: String) -> Job {
+        let now = DateTime::from_millis(get_timestamp() as i64);
+        return Job { 
+            _id: None, 
+            name, 
+            social_network, 
+            seed, 
+            status: JobStatus::Active, 
+            creation_time: now,
+            update_time: Some(now)
+        };
+    }
+}
+
+impl DBCollection for Job {
+    fn get_collection() -> String {
+        return DATABASE_COLLECTIONS::JOBS.to_string();
+    }
+}
+
+//number of job tasks by status, failed includes dead letters
+#[derive(Debug, Default, Clone)]
+pub struct JobProgress {
+    pub pending: u64,
+    pub processing: u64,
+    pub processed: u64,
+    pub failed: u64,
+    pub cancelled: u64
+}
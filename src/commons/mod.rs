@@ -1,3 +1,6 @@
 pub mod social_network;
 pub mod entity;
-pub mod parsing_tasks;
\ No newline at end of file
+pub mod parsing_tasks;
+pub mod community;
+pub mod engagement;
+pub mod job;
\ No newline at end of file
@@ -1,4 +1,5 @@
 use mongodb::bson::oid::ObjectId;
+use rand::Rng;
 use serde::{Serialize, Deserialize};
 use strum::EnumIter;
 
@@ -6,11 +7,19 @@ use crate::{reddit::reddit_parsing_task::{RedditParsingTask}, client::db::client
 
 use super::social_network::SocialNetworkEnum;
 
-#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug, EnumIter, strum::Display)]
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug, EnumIter, strum::Display, strum::EnumString)]
 pub enum ParsingTaskStatus {
     New, 
     Processing,
-    Processed
+    Processed,
+    //permanent error, retry would not help
+    Failed,
+    //transient errors exceeded max attempts of retry policy
+    DeadLetter,
+    //job of the task was cancelled
+    Cancelled,
+    //task was returned to New while an equal task was already New
+    Duplicate
 }
 
 #[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
@@ -21,7 +30,97 @@ pub struct ParsingTask {
     pub parameters: ParsingTaskParameters,
     pub action_type: String,
     pub social_network: SocialNetworkEnum,
-    pub status: ParsingTaskStatus
+    pub status: ParsingTaskStatus,
+    #[serde(default)]
+    pub seed: Option<TaskSeed>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease: Option<TaskLease>,
+    #[serde(default)]
+    pub retry: TaskRetry,
+    //tasks with higher priority are claimed first, then by execution_time
+    #[serde(default)]
+    pub priority: i64,
+    //job the task belongs to, passed to every spawned task
+    #[serde(default)]
+    pub job: Option<ObjectId>,
+    //number of listing pages fetched before this one
+    #[serde(default)]
+    pub page: u64
+}
+
+//task in Processing status is owned by the worker until the lease expires
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
+pub struct TaskLease {
+    pub owner: String,
+    pub expires_at: u64,
+    //account the task is parsed with, set when parser takes an account for it
+    #[serde(default)]
+    pub account: Option<String>
+}
+
+//settings seed the task was spawned from
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
+pub struct TaskSeed {
+    pub name: String,
+    //number of discovery hops from the seed, 0 for tasks of the seed itself
+    pub depth: u64
+}
+
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug, Default)]
+pub struct TaskRetry {
+    pub attempts: u64,
+    pub last_error: Option<String>,
+    //task is not claimed before this time
+    pub next_attempt_time: Option<u64>
+}
+
+pub enum TaskFailure {
+    Transient(String),
+    Permanent(String),
+    //request was rejected by rate limit, task is retried without using up an attempt
+    RateLimited(String)
+}
+
+//failed task is retried with exponential backoff until max_attempts
+#[derive(Serialize, Deserialize, Clone, Hash, Eq, PartialEq, Debug)]
+#[serde(default)]
+pub struct RetryPolicy {
+    pub max_attempts: u64,
+    pub base_delay_seconds: u64,
+    pub max_delay_seconds: u64
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self { 
+            max_attempts: 5, 
+            base_delay_seconds: 30, 
+            max_delay_seconds: 3600 
+        }
+    }
+}
+
+impl RetryPolicy {
+    //delay is doubled with every attempt, random jitter takes up to half of it
+    pub fn get_next_attempt_time(&self, attempts: u64, now: u64) -> u64 {
+        let delay = self.base_delay_seconds
+            .saturating_mul(1 << attempts.min(32))
+            .min(self.max_delay_seconds) * 1000;
+        return now + delay / 2 + rand::thread_rng().gen_range(0..=delay / 2);
+    }
+}
+
+impl TaskSeed {
+    pub fn new(name: String) -> TaskSeed {
+        return TaskSeed { name, depth: 0 };
+    }
+}
+
+impl ParsingTask {
+    //tasks requesting the same data of the same social network have the same key
+    pub fn get_key(&self) -> String {
+        return format!("{}:{}", self.social_network, self.parameters.get_key());
+    }
 }
 
 impl DBCollection for ParsingTask {
@@ -43,10 +142,60 @@ impl ParsingTaskParameters {
         }
     }
 
+    pub fn get_key(&self) -> String {
+        return match self {
+            ParsingTaskParameters::Reddit(reddit_parameters) => reddit_parameters.get_key()
+        }
+    }
+
+    pub fn is_one_shot(&self) -> bool {
+        return match self {
+            ParsingTaskParameters::Reddit(reddit_parameters) => reddit_parameters.is_one_shot()
+        }
+    }
+
     pub fn as_ref_reddit(&self) -> &RedditParsingTask {
         return match self {
             ParsingTaskParameters::Reddit(reddit_parameters) => reddit_parameters,
             _ => panic!("wrong method dispatch")
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_000_000;
+
+    fn assert_delay_bounds(policy: &RetryPolicy, attempts: u64, delay_seconds: u64) {
+        let delay = delay_seconds * 1000;
+        for _ in 0..100 {
+            let next_attempt_time = policy.get_next_attempt_time(attempts, NOW);
+            assert!(next_attempt_time >= NOW + delay / 2, "attempt {} is earlier than half of the delay", attempts);
+            assert!(next_attempt_time <= NOW + delay, "attempt {} is later than the delay", attempts);
+        }
+    }
+
+    #[test]
+    fn delay_is_doubled_with_every_attempt() {
+        let policy = RetryPolicy::default();
+        assert_delay_bounds(&policy, 1, 60);
+        assert_delay_bounds(&policy, 2, 120);
+        assert_delay_bounds(&policy, 3, 240);
+    }
+
+    #[test]
+    fn delay_is_capped_by_max_delay() {
+        let policy = RetryPolicy::default();
+        assert_delay_bounds(&policy, 10, policy.max_delay_seconds);
+        //shift is capped, so large attempts do not overflow
+        assert_delay_bounds(&policy, u64::MAX, policy.max_delay_seconds);
+    }
+
+    #[test]
+    fn zero_delay_has_no_jitter() {
+        let policy = RetryPolicy { base_delay_seconds: 0, ..RetryPolicy::default() };
+        assert_eq!(policy.get_next_attempt_time(3, NOW), NOW);
+    }
+}
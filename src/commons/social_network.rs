@@ -43,11 +43,20 @@ lazy_static! {
 pub trait SocialNetwork {
     async fn auth(&self, account_data: AccountDataPtr, client: ReqwestClientPtr) -> Result<AccountSession, Box<dyn Error + Send + Sync>>;
     async fn parse(&self, parsing_task: ParsingTask, account: AccountPtr);
-    fn apply_settings(& mut self, settings: SettingsPtr);
+    fn apply_settings(&self, settings: SettingsPtr);
     fn prepare_parsing_tasks(&self, settings: SettingsPtr) ->  Result<Vec<ParsingTask>, Box<dyn Error>>;
     fn prepare_accounts(&self, settings: SettingsPtr) -> Result<Vec<Account>, Box<dyn Error>>;
 }
 
+pub fn apply_social_networks_settings(settings: SettingsPtr) {
+    for social_network in settings.social_network_settings.keys() {
+        SOCIAL_NETWORKS
+            .get(social_network)
+            .expect("No such social network!")
+            .apply_settings(settings.clone());
+    }
+}
+
 pub fn dispatch_social_network<T, R, F>(
     data: T, 
     social_network: SocialNetworkEnum, 
@@ -2,18 +2,20 @@
 #![feature(async_closure)]
 #![feature(once_cell)]
 #![feature(unwrap_infallible)]
-#![feature(hash_drain_filter)]
 #![feature(linked_list_cursors)]
 
 use std::slice::Split;
 use std::sync::Arc;
 use std::time::Duration;
-use std::{io, env};
+use std::{io, env, process};
 use client::parser_v2::account_manager::account_pool_builder::AccountPoolBuilder;
 use client::parser_v2::parser::ParserBuilder;
 use client::parser_v2::statistics::{STATISTICS};
-use client::parser_v2::task_publisher::{TaskPublisherMod, TaskPublisherBuilder};
-use client::settings::{get_settings};
+use client::parser_v2::shutdown::Shutdown;
+use client::parser_v2::task_publisher::TaskPublisherBuilder;
+use client::settings::{get_settings, SettingsPtr};
+use client::commands::run_command;
+use commons::social_network::apply_social_networks_settings;
 use log::LevelFilter;
 use log4rs::append::console::ConsoleAppender;
 use log4rs::append::file::FileAppender;
@@ -26,15 +28,33 @@ mod client;
 mod reddit;
 mod commons;
 
-#[tokio::main(flavor = "multi_thread", worker_threads = 50)]
-async fn main() -> Result<(), io::Error>{
+fn main() -> Result<(), io::Error>{
     init_logger();
-    run_statistics_printing();
     let settings = get_settings();
+    return tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(settings.general_settings.concurrency.worker_threads)
+        .enable_all()
+        .build()?
+        .block_on(run(settings));
+}
+
+async fn run(settings: SettingsPtr) -> Result<(), io::Error> {
+    match run_command(&env::args().skip(1).collect::<Vec<_>>(), settings.clone()).await {
+        Ok(true) => return Ok(()),
+        Ok(false) => {},
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(2);
+        }
+    }
+    run_statistics_printing();
+    apply_social_networks_settings(settings.clone());
     let mut parser = ParserBuilder::new(
-        TaskPublisherBuilder::new(TaskPublisherMod::Manual, settings.clone(), 1000),
-        AccountPoolBuilder::new(settings.clone())
-    ).build().await;
+        TaskPublisherBuilder::new(settings.clone(), settings.general_settings.concurrency.publisher_batch_size),
+        AccountPoolBuilder::new(settings.clone()),
+        settings.clone(),
+        Shutdown::listen_signals()
+    ).build().await.map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
     parser.start().await;
     Ok(())
 } 
@@ -1,5 +1,7 @@
 pub mod reddit_comment;
 pub mod reddit_post;
+pub mod reddit_user;
+pub mod reddit_subreddit;
 pub mod reddit_auth;
 pub mod reddit_listing;
 pub mod reddit_pages;
@@ -1,12 +1,17 @@
 use mongodb::bson::DateTime;
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, Deserializer, de};
+use serde_json::Value;
 
 use crate::commons::{entity::{Entity, EntityType}, social_network::SocialNetworkEnum};
 
+use super::{reddit_listing::Listing, reddit_post::get_content_status};
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Comment {
     pub id: String,
     pub parent_id: String,
+    pub link_id: Option<String>,
+    pub depth: Option<u64>,
     #[serde(rename = "created")] 
     pub timestamp: Option<f64>,
     pub score: Option<i64>, 
@@ -17,14 +22,84 @@ pub struct Comment {
     #[serde(rename = "subreddit_name_prefixed")]
     pub source: Option<String>,
     pub body: Option<String>, 
+    //reddit sends "" instead of listing when there are no replies
+    #[serde(default, deserialize_with = "deserialize_replies")]
+    pub replies: Option<Listing<CommentItem>>
+}
+
+//"more" stub of collapsed branch, empty children means "continue this thread" link
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MoreComments {
+    pub id: String,
+    pub parent_id: String,
+    pub link_id: Option<String>,
+    pub depth: Option<u64>,
+    pub count: u64,
+    pub children: Vec<String>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum CommentItem {
+    More(MoreComments),
+    Comment(Comment)
+}
+
+impl CommentItem {
+
+    pub fn flatten(self, comments: &mut Vec<Comment>) {
+        if let CommentItem::Comment(mut comment) = self {
+            if let Some(replies) = comment.replies.take() {
+                replies.data.children.into_iter().for_each(|item| item.data.flatten(comments));
+            }
+            comments.push(comment);
+        }
+    }
+
+    //depth of comments fetched from the middle of a thread starts from 0
+    pub fn offset_depth(&mut self, offset: u64) {
+        match self {
+            CommentItem::Comment(comment) => {
+                comment.depth = comment.depth.map(|depth| depth + offset);
+                if let Some(replies) = comment.replies.as_mut() {
+                    replies.data.children.iter_mut().for_each(|item| item.data.offset_depth(offset));
+                }
+            },
+            CommentItem::More(more_comments) => more_comments.depth = more_comments.depth.map(|depth| depth + offset)
+        }
+    }
+
+    pub fn walk<'a>(&'a self, comments: &mut Vec<&'a Comment>, more: &mut Vec<&'a MoreComments>) {
+        match self {
+            CommentItem::Comment(comment) => {
+                if let Some(replies) = comment.replies.as_ref() {
+                    replies.data.children.iter().for_each(|item| item.data.walk(comments, more));
+                }
+                comments.push(comment);
+            },
+            CommentItem::More(more_comments) => more.push(more_comments)
+        }
+    }
+}
+
+fn deserialize_replies<'de, D>(deserializer: D) -> Result<Option<Listing<CommentItem>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Value = Deserialize::deserialize(deserializer)?;
+    return match value {
+        Value::Object(_) => serde_json::from_value(value).map(Some).map_err(de::Error::custom),
+        _ => Ok(None)
+    };
 }
 
 impl From<Comment> for Entity {
     fn from(comment: Comment) -> Self {
+        let content_status = get_content_status(&comment.body, &comment.author_name, &None);
         return Entity {
             _id: None,
             id: comment.id,
-            source: comment.parent_id,
+            source: comment.parent_id.clone(),
             source_followers: None,
             date_time: DateTime::from_millis(comment.timestamp.unwrap_or(0.0) as i64 * 1000),
             entity_type: EntityType::Comment,
@@ -34,7 +109,13 @@ impl From<Comment> for Entity {
             author_name: comment.author_name, 
             rating: comment.score, 
             images: Vec::new(),
+            content_status,
             social_network: SocialNetworkEnum::Reddit,
+            //comment parents come as fullnames, stored comment ids are not prefixed
+            parent_id: Some(comment.parent_id.strip_prefix("t1_").unwrap_or(&comment.parent_id).to_string()),
+            root_id: comment.link_id,
+            depth: comment.depth,
+            extension: None,
         }
     }
 }
\ No newline at end of file
@@ -1,7 +1,7 @@
-use serde::{Serialize, Deserialize, Deserializer};
+use serde::{Serialize, Deserialize, Deserializer, de};
 use serde_json::Value;
 
-use super::{reddit_listing::Listing, reddit_comment::Comment, reddit_post::Post};
+use super::{reddit_listing::{Listing, Children}, reddit_comment::{Comment, CommentItem, MoreComments}, reddit_post::Post, reddit_user::User, reddit_subreddit::Subreddit};
 
 #[derive(Serialize, Clone, Debug)]
 pub struct ThreadPage {
@@ -11,9 +11,74 @@ pub struct ThreadPage {
 #[derive(Serialize, Clone, Debug)]
 pub struct CommentPage {
     pub post: Listing<Post>,
+    pub comments: Listing<CommentItem>
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct UserCommentsPage {
     pub comments: Listing<Comment>
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct UserPage {
+    pub kind: Option<String>,
+    pub data: User
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SubredditPage {
+    pub kind: Option<String>,
+    pub data: Subreddit
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MoreChildrenPage {
+    pub things: Vec<Children<CommentItem>>
+}
+
+impl CommentPage {
+
+    pub fn get_comments(self) -> Vec<Comment> {
+        let mut comments = Vec::new();
+        self.comments.data.children.into_iter().for_each(|item| item.data.flatten(&mut comments));
+        return comments;
+    }
+
+    pub fn walk(&self) -> (Vec<&Comment>, Vec<&MoreComments>) {
+        let (mut comments, mut more) = (Vec::new(), Vec::new());
+        self.comments.data.children.iter().for_each(|item| item.data.walk(&mut comments, &mut more));
+        return (comments, more);
+    }
+
+    pub fn get_more_comments(&self) -> Vec<&MoreComments> {
+        return self.walk().1;
+    }
+
+    pub fn with_depth_offset(mut self, offset: u64) -> CommentPage {
+        self.comments.data.children.iter_mut().for_each(|item| item.data.offset_depth(offset));
+        return self;
+    }
+}
+
+impl MoreChildrenPage {
+
+    pub fn get_comments(self) -> Vec<Comment> {
+        let mut comments = Vec::new();
+        self.things.into_iter().for_each(|item| item.data.flatten(&mut comments));
+        return comments;
+    }
+
+    pub fn walk(&self) -> (Vec<&Comment>, Vec<&MoreComments>) {
+        let (mut comments, mut more) = (Vec::new(), Vec::new());
+        self.things.iter().for_each(|item| item.data.walk(&mut comments, &mut more));
+        return (comments, more);
+    }
+
+    pub fn get_more_comments(&self) -> Vec<&MoreComments> {
+        return self.walk().1;
+    }
+}
+
 impl<'de> Deserialize<'de> for ThreadPage {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -24,20 +89,98 @@ impl<'de> Deserialize<'de> for ThreadPage {
     }
 }
 
+impl<'de> Deserialize<'de> for UserCommentsPage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let listing: Listing<Comment> = Deserialize::deserialize(deserializer)?;
+        return Ok(UserCommentsPage{ comments: listing });
+    }
+}
+
 impl<'de> Deserialize<'de> for CommentPage {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         let listings: Vec<Value> = Deserialize::deserialize(deserializer)?;
-        let children: Listing<Post> = Deserialize::deserialize(listings
-            .get(0)
-            .unwrap()
-        ).expect("parsing error");
-        let comments: Listing<Comment> = Deserialize::deserialize(listings
-            .get(1)
-            .unwrap()
-        ).expect("parsing error");
+        if listings.len() < 2 {
+            return Err(de::Error::invalid_length(listings.len(), &"post and comments listings"));
+        }
+        let children: Listing<Post> = Deserialize::deserialize(&listings[0]).map_err(de::Error::custom)?;
+        let comments: Listing<CommentItem> = Deserialize::deserialize(&listings[1]).map_err(de::Error::custom)?;
         return Ok(CommentPage{ post: children, comments });
     }
-}
\ No newline at end of file
+}
+
+impl<'de> Deserialize<'de> for MoreChildrenPage {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let response: Value = Deserialize::deserialize(deserializer)?;
+        let things: Vec<Children<CommentItem>> = Deserialize::deserialize(response
+            .pointer("/json/data/things")
+            .unwrap_or(&Value::Array(Vec::new()))
+        ).map_err(de::Error::custom)?;
+        return Ok(MoreChildrenPage{ things });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //post with a comment having a reply and a collapsed branch, a comment without replies
+    //and a "continue this thread" stub
+    const COMMENT_PAGE: &str = r#"[
+        {"kind": "Listing", "data": {"after": null, "children": [
+            {"kind": "t3", "data": {"name": "t3_post", "title": "title", "ups": 10}}
+        ]}},
+        {"kind": "Listing", "data": {"after": null, "children": [
+            {"kind": "t1", "data": {"id": "first", "parent_id": "t3_post", "link_id": "t3_post", "depth": 0, "body": "first", 
+                "replies": {"kind": "Listing", "data": {"after": null, "children": [
+                    {"kind": "t1", "data": {"id": "reply", "parent_id": "t1_first", "link_id": "t3_post", "depth": 1, "body": "reply", "replies": ""}},
+                    {"kind": "more", "data": {"id": "collapsed", "parent_id": "t1_first", "depth": 1, "count": 2, "children": ["a", "b"]}}
+                ]}}
+            }},
+            {"kind": "t1", "data": {"id": "second", "parent_id": "t3_post", "link_id": "t3_post", "depth": 0, "body": "second", "replies": ""}},
+            {"kind": "more", "data": {"id": "_", "parent_id": "t1_second", "depth": 1, "count": 0, "children": []}}
+        ]}}
+    ]"#;
+
+    fn get_page() -> CommentPage {
+        return serde_json::from_str(COMMENT_PAGE).expect("unable to parse comment page");
+    }
+
+    #[test]
+    fn nested_replies() {
+        let page = get_page();
+        let (comments, more) = page.walk();
+        let ids: Vec<&str> = comments.iter().map(|comment| comment.id.as_str()).collect();
+        assert_eq!(ids, vec!["reply", "first", "second"]);
+        //empty string replies are no replies
+        assert!(comments.iter().filter(|comment| comment.id != "first").all(|comment| comment.replies.is_none()));
+        assert_eq!(more.len(), 2);
+        assert_eq!(more[0].children, vec!["a", "b"]);
+        //"continue this thread" stub has no children
+        assert!(more[1].children.is_empty());
+        assert_eq!(page.get_comments().len(), 3);
+    }
+
+    #[test]
+    fn depth_offset() {
+        let page = get_page().with_depth_offset(3);
+        let (comments, more) = page.walk();
+        let depths: Vec<Option<u64>> = comments.iter().map(|comment| comment.depth).collect();
+        assert_eq!(depths, vec![Some(4), Some(3), Some(3)]);
+        assert_eq!(more[0].depth, Some(4));
+    }
+
+    #[test]
+    fn invalid_page_is_error() {
+        assert!(serde_json::from_str::<CommentPage>("[]").is_err());
+        assert!(serde_json::from_str::<CommentPage>(r#"[{"kind": "Listing"}, {"kind": "Listing"}]"#).is_err());
+    }
+}
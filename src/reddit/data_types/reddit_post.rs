@@ -1,7 +1,10 @@
-use mongodb::bson::DateTime;
-use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
 
-use crate::commons::{entity::{Entity, EntityType}, social_network::SocialNetworkEnum};
+use mongodb::bson::{self, DateTime};
+use serde::{Serialize, Deserialize, Deserializer};
+use serde_json::Value;
+
+use crate::commons::{entity::{Entity, EntityType, ContentStatus}, engagement::EngagementSnapshot, social_network::SocialNetworkEnum};
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct  Post {
@@ -21,8 +24,122 @@ pub struct  Post {
     #[serde(alias = "author")]
     pub author_name: Option<String>, 
     pub ups: i64,
-    pub preview: Option<Preview>
+    pub preview: Option<Preview>,
+    pub crosspost_parent_list: Option<Vec<Crosspost>>,
+    pub crosspost_parent: Option<String>,
+    pub link_flair_text: Option<String>,
+    pub over_18: Option<bool>,
+    pub spoiler: Option<bool>,
+    pub num_comments: Option<u64>,
+    pub upvote_ratio: Option<f64>,
+    pub url: Option<String>,
+    pub permalink: Option<String>,
+    pub domain: Option<String>,
+    pub is_video: Option<bool>,
+    pub media_metadata: Option<HashMap<String, MediaMetadata>>,
+    //reddit sends false for not edited posts and edit timestamp otherwise
+    #[serde(default, deserialize_with = "deserialize_edited")]
+    pub edited: Option<f64>,
+    pub stickied: Option<bool>,
+    pub locked: Option<bool>,
+    pub removed_by_category: Option<String>
+
+}
+
+//gallery item, fields are named as in reddit response
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediaMetadata {
+    pub status: Option<String>,
+    #[serde(rename = "e")]
+    pub media_type: Option<String>,
+    #[serde(rename = "m")]
+    pub mime: Option<String>,
+    #[serde(rename = "s")]
+    pub source: Option<MediaSource>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct MediaSource {
+    #[serde(rename = "u")]
+    pub url: Option<String>,
+    pub gif: Option<String>,
+    pub mp4: Option<String>
+}
+
+//reddit specific post fields stored in Entity extension
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RedditPostExtension {
+    pub link_flair_text: Option<String>,
+    pub over_18: Option<bool>,
+    pub spoiler: Option<bool>,
+    pub num_comments: Option<u64>,
+    pub upvote_ratio: Option<f64>,
+    pub url: Option<String>,
+    pub permalink: Option<String>,
+    pub domain: Option<String>,
+    pub is_video: Option<bool>,
+    pub media_metadata: Option<HashMap<String, MediaMetadata>>,
+    pub crosspost_parent: Option<String>,
+    pub edited: Option<DateTime>,
+    pub stickied: Option<bool>,
+    pub locked: Option<bool>
+}
+
+impl RedditPostExtension {
+    pub fn from_entity(entity: &Entity) -> Option<RedditPostExtension> {
+        return entity.extension.clone().and_then(|extension| bson::from_document(extension).ok());
+    }
+}
 
+pub fn get_engagement_snapshot(entity: &Entity) -> EngagementSnapshot {
+    let extension = RedditPostExtension::from_entity(entity);
+    return EngagementSnapshot::new(
+        entity, 
+        extension.as_ref().and_then(|extension| extension.num_comments), 
+        extension.as_ref().and_then(|extension| extension.upvote_ratio)
+    );
+}
+
+//reddit keeps removed and deleted things but replaces their content with placeholders
+pub fn get_content_status(content: &Option<String>, author_name: &Option<String>, removed_by_category: &Option<String>) -> ContentStatus {
+    return match (content.as_deref(), author_name.as_deref(), removed_by_category.as_deref()) {
+        (_, _, Some("deleted")) | (Some("[deleted]"), _, _) => ContentStatus::Deleted,
+        (_, _, Some(_)) | (Some("[removed]"), _, _) => ContentStatus::Removed,
+        (_, Some("[deleted]"), _) => ContentStatus::Deleted,
+        _ => ContentStatus::Active
+    };
+}
+
+fn deserialize_edited<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let value: Value = Deserialize::deserialize(deserializer)?;
+    return Ok(value.as_f64());
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Crosspost {
+    #[serde(rename = "subreddit_name_prefixed")]
+    pub source: Option<String>
+}
+
+impl Post {
+    pub fn get_crosspost_sources(&self) -> Vec<String> {
+        return self.crosspost_parent_list
+            .iter()
+            .flatten()
+            .filter_map(|crosspost| crosspost.source.clone())
+            .collect();
+    }
+
+    pub fn get_gallery_urls(&self) -> Vec<String> {
+        return self.media_metadata
+            .iter()
+            .flat_map(|media_metadata| media_metadata.values())
+            .filter_map(|media| media.source.as_ref().and_then(|source| source.url.clone().or_else(|| source.gif.clone())))
+            .collect();
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -42,6 +159,8 @@ pub struct Source {
 
 impl From<Post> for Entity {
     fn from(children: Post) -> Self {
+        let gallery_urls = children.get_gallery_urls();
+        let content_status = get_content_status(&children.self_text, &children.author_name, &children.removed_by_category);
         return Entity {
             _id: None,
             id: children.id,
@@ -59,8 +178,64 @@ impl From<Post> for Entity {
                 .map_or(Vec::new(), |v| v.images)
                 .into_iter()
                 .map(|v| v.source.url)
+                .chain(gallery_urls)
                 .collect(),
+            content_status,
             social_network: SocialNetworkEnum::Reddit,
+            parent_id: None,
+            root_id: None,
+            depth: None,
+            extension: bson::to_document(&RedditPostExtension { 
+                link_flair_text: children.link_flair_text, 
+                over_18: children.over_18, 
+                spoiler: children.spoiler, 
+                num_comments: children.num_comments, 
+                upvote_ratio: children.upvote_ratio, 
+                url: children.url, 
+                permalink: children.permalink, 
+                domain: children.domain, 
+                is_video: children.is_video, 
+                media_metadata: children.media_metadata, 
+                crosspost_parent: children.crosspost_parent, 
+                edited: children.edited.map(|edited| DateTime::from_millis(edited as i64 * 1000)), 
+                stickied: children.stickied, 
+                locked: children.locked 
+            }).ok(),
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_status(content: Option<&str>, author_name: Option<&str>, removed_by_category: Option<&str>) -> ContentStatus {
+        return get_content_status(
+            &content.map(String::from), 
+            &author_name.map(String::from), 
+            &removed_by_category.map(String::from)
+        );
+    }
+
+    #[test]
+    fn active_content() {
+        assert_eq!(get_status(Some("text"), Some("author"), None), ContentStatus::Active);
+        assert_eq!(get_status(None, Some("author"), None), ContentStatus::Active);
+    }
+
+    #[test]
+    fn deleted_by_author() {
+        assert_eq!(get_status(Some("[deleted]"), Some("[deleted]"), None), ContentStatus::Deleted);
+        assert_eq!(get_status(Some("text"), Some("[deleted]"), None), ContentStatus::Deleted);
+        assert_eq!(get_status(Some(""), Some("[deleted]"), Some("deleted")), ContentStatus::Deleted);
+    }
+
+    #[test]
+    fn removed_by_moderators() {
+        assert_eq!(get_status(Some("[removed]"), Some("author"), None), ContentStatus::Removed);
+        assert_eq!(get_status(Some(""), Some("author"), Some("moderator")), ContentStatus::Removed);
+        //removed content of deleted account is still removed
+        assert_eq!(get_status(Some("[removed]"), Some("[deleted]"), None), ContentStatus::Removed);
+    }
+}
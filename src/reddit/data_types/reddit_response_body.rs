@@ -2,14 +2,22 @@ use super::reddit_pages::{self};
 
 pub enum ResponseBody {
     Thread(Result<reddit_pages::ThreadPage, reqwest::Error>),
-    Comments(Result<reddit_pages::CommentPage, reqwest::Error>)
+    Comments(Result<reddit_pages::CommentPage, reqwest::Error>),
+    MoreComments(Result<reddit_pages::MoreChildrenPage, reqwest::Error>),
+    UserComments(Result<reddit_pages::UserCommentsPage, reqwest::Error>),
+    User(Result<reddit_pages::UserPage, reqwest::Error>),
+    Subreddit(Result<reddit_pages::SubredditPage, reqwest::Error>)
 }
 
 impl ResponseBody {
-    pub fn is_ok(&self) -> bool {
+    pub fn get_error(&self) -> Option<&reqwest::Error> {
         return match self {
-            ResponseBody::Thread(thread) => thread.is_ok(),
-            ResponseBody::Comments(comments) => comments.is_ok(),
+            ResponseBody::Thread(thread) => thread.as_ref().err(),
+            ResponseBody::Comments(comments) => comments.as_ref().err(),
+            ResponseBody::MoreComments(comments) => comments.as_ref().err(),
+            ResponseBody::UserComments(comments) => comments.as_ref().err(),
+            ResponseBody::User(user) => user.as_ref().err(),
+            ResponseBody::Subreddit(subreddit) => subreddit.as_ref().err(),
         }
     }
 }
\ No newline at end of file
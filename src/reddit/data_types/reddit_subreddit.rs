@@ -0,0 +1,40 @@
+use mongodb::bson::DateTime;
+use serde::{Serialize, Deserialize};
+
+use crate::{commons::{community::CommunitySnapshot, social_network::SocialNetworkEnum}, utils::time::get_timestamp};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Subreddit {
+    #[serde(rename = "name")]
+    pub id: String,
+    #[serde(rename = "display_name_prefixed")]
+    pub name: String,
+    #[serde(rename = "created_utc")] 
+    pub timestamp: Option<f64>,
+    pub title: Option<String>,
+    pub public_description: Option<String>,
+    //sidebar markdown
+    pub description: Option<String>,
+    pub subscribers: Option<u64>,
+    #[serde(alias = "accounts_active")]
+    pub active_user_count: Option<u64>,
+    pub over18: Option<bool>
+}
+
+impl From<Subreddit> for CommunitySnapshot {
+    fn from(subreddit: Subreddit) -> Self {
+        return CommunitySnapshot {
+            _id: None,
+            id: subreddit.id,
+            name: subreddit.name,
+            social_network: SocialNetworkEnum::Reddit,
+            snapshot_time: DateTime::from_millis(get_timestamp() as i64),
+            creation_date: DateTime::from_millis(subreddit.timestamp.unwrap_or(0.0) as i64 * 1000),
+            title: subreddit.title,
+            description: subreddit.public_description,
+            subscribers: subreddit.subscribers,
+            active_users: subreddit.active_user_count,
+            nsfw: subreddit.over18
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,43 @@
+use mongodb::bson::DateTime;
+use serde::{Serialize, Deserialize};
+
+use crate::commons::{entity::{Entity, EntityType, ContentStatus}, social_network::SocialNetworkEnum};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct User {
+    pub id: String,
+    pub name: String,
+    #[serde(rename = "created")] 
+    pub timestamp: Option<f64>,
+    pub link_karma: Option<i64>,
+    pub comment_karma: Option<i64>,
+    pub total_karma: Option<i64>,
+    pub icon_img: Option<String>
+}
+
+impl From<User> for Entity {
+    fn from(user: User) -> Self {
+        return Entity {
+            _id: None,
+            id: format!("t2_{}", user.id),
+            source: format!("u/{}", user.name),
+            source_followers: None,
+            date_time: DateTime::from_millis(user.timestamp.unwrap_or(0.0) as i64 * 1000),
+            entity_type: EntityType::User,
+            author_id: Some(format!("t2_{}", user.id)), 
+            title: None, 
+            content: None, 
+            author_name: Some(user.name), 
+            rating: user.total_karma.or(
+                user.link_karma.zip(user.comment_karma).map(|(link_karma, comment_karma)| link_karma + comment_karma)
+            ), 
+            images: user.icon_img.into_iter().filter(|url| !url.is_empty()).collect(),
+            content_status: ContentStatus::Active,
+            social_network: SocialNetworkEnum::Reddit,
+            parent_id: None,
+            root_id: None,
+            depth: None,
+            extension: None,
+        }
+    }
+}
\ No newline at end of file
@@ -1,3 +1,7 @@
 pub mod reddit;
 pub mod reddit_parsing_task;
+pub mod reddit_settings;
+pub mod reddit_discovery;
+pub mod reddit_revisit;
+pub mod reddit_schedule;
 pub mod data_types;
\ No newline at end of file
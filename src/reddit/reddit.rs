@@ -1,42 +1,60 @@
+use std::collections::HashSet;
 use std::error::Error;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Duration};
-use futures::FutureExt;
 use log::{error, info};
+use mongodb::bson::doc;
 use reqwest::{Response, StatusCode};
 use crate::client::parser_v2::account_manager::account::{AccountSession, Account, AccountPtr, AccountDataPtr, ReqwestClientPtr};
 use crate::client::parser_v2::statistics::STATISTICS;
 use crate::client::settings::{SettingsPtr};
 use crate::utils::time::get_timestamp;
-use crate::client::db::tasks_db::{update_tasks_with_status, insert_tasks};
-use crate::client::db::entities_db::{insert_with_replace};
+use crate::client::db::tasks_db::{update_task_with_status, insert_tasks, fail_task, count_seed_tasks_since};
+use crate::client::db::entities_db::{insert_with_replace, insert_engagement_snapshots};
+use crate::client::db::communities_db::{insert_community_snapshots, register_discovered_community, update_discovered_community_status};
 use crate::commons::social_network::*;
 use crate::commons::parsing_tasks::*;
-use crate::commons::entity::Entity;
+use crate::commons::entity::{Entity, EntityType};
+use crate::commons::community::{DiscoveredCommunity, DiscoveryStatus};
 use super::{
-    reddit_parsing_task::RedditParsingTask, 
+    reddit_parsing_task::{RedditParsingTask, RedditListing}, 
+    reddit_settings::{RedditSettings, RedditSeed},
+    reddit_discovery::{get_candidates, is_allowed, normalize_thread, is_aggregate_thread}, 
+    reddit_revisit::PostObservation, 
     data_types::{
         reddit_auth::AuthResponse, 
         reddit_pages::{
             ThreadPage, 
-            CommentPage
+            CommentPage, 
+            MoreChildrenPage, 
+            UserCommentsPage, 
+            UserPage, 
+            SubredditPage
         }, 
+        reddit_post::get_engagement_snapshot,
+        reddit_comment::MoreComments,
+        reddit_subreddit::Subreddit,
         reddit_response_body::ResponseBody
     }
 };
 
+//reddit api accepts at most 100 ids per morechildren request
+const MORE_CHILDREN_LIMIT: usize = 100;
+
 pub struct Reddit {
     pub auth_url: String,
-    pub enable_comments_parsing: bool
+    pub settings: RwLock<RedditSettings>,
+    pub retry_policy: RwLock<RetryPolicy>
 }
 
 impl Default for Reddit {
     fn default() -> Self {
         Self { 
             auth_url: String::from("https://www.reddit.com/api/v1/access_token") ,
-            enable_comments_parsing: true
+            settings: RwLock::new(RedditSettings::default()),
+            retry_policy: RwLock::new(RetryPolicy::default())
         }
     }
 }
@@ -76,53 +94,57 @@ impl SocialNetwork for Reddit {
     }
 
     async fn parse(&self, parsing_task: ParsingTask, account: AccountPtr) {
-        let reqwest_client = account.reqwest_client.clone().expect("Unauthorized account in Reddit parser");
-        let mut token = String::from("");
-        {
-            let rg_session = account.session.read().await;
-            token = rg_session.as_ref().expect("Unauthorized account in Reddit parser").token.clone();
+        let mut response = Reddit::send_request(&parsing_task, &account).await;
+        //403 may be caused by expired token, so the request is repeated once after reauthentication
+        let mut reauthorized = false;
+        if matches!(&response, Ok(response) if response.status() == StatusCode::FORBIDDEN) {
+            STATISTICS.increase_access_failed_parsing_tasks();
+            reauthorized = account.try_auth().await.is_ok();
+            if reauthorized {
+                response = Reddit::send_request(&parsing_task, &account).await;
+            }
         }
-        reqwest_client
-            .get(parsing_task.parameters.as_ref_reddit().to_url())
-            .bearer_auth(token)
-            .send()
-            .then( 
-                move |response| Reddit::process_response(parsing_task, account.clone(), response)
-            ).await;
+        self.process_response(parsing_task, account, response, reauthorized).await;
     }
 
-    fn apply_settings(&mut self, settings: SettingsPtr) {
-        self.enable_comments_parsing = settings
-            .social_network_settings.get(&SocialNetworkEnum::Reddit).unwrap()
-            .additional_properties.get("enable_comments_parsing").unwrap()
-            .as_bool().unwrap();
+    fn apply_settings(&self, settings: SettingsPtr) {
+        let reddit_settings = RedditSettings::new(
+            settings.social_network_settings.get(&SocialNetworkEnum::Reddit).unwrap()
+        ).expect("unable to parse reddit settings");
+        *self.settings.write().unwrap() = reddit_settings;
+        *self.retry_policy.write().unwrap() = settings.general_settings.retry.clone();
     }
 
     fn prepare_parsing_tasks(&self, settings: SettingsPtr) ->  Result<Vec<ParsingTask>, Box<dyn Error>> {
-        let tasks = &settings.social_network_settings
-            .get(&SocialNetworkEnum::Reddit).unwrap().parsing_tasks;
+        let reddit_settings = RedditSettings::new(
+            settings.social_network_settings.get(&SocialNetworkEnum::Reddit).unwrap()
+        )?;
 
-        let mut parsing_tasks: Vec<RedditParsingTask> = Vec::new();
+        let mut parsing_tasks: Vec<ParsingTask> = Vec::new();
 
-        for task in tasks {
-            parsing_tasks.extend(RedditParsingTask::get_all(
-                &task.get("thread").unwrap().as_str().unwrap().to_string()
-            ));
+        for (name, seed) in reddit_settings.seeds.iter() {
+            parsing_tasks.extend(
+                Reddit::get_seed_tasks(seed)
+                    .into_iter()
+                    .map(|item| ParsingTask{ 
+                        _id: None, 
+                        execution_time: get_timestamp(), 
+                        action_type: item.to_string(), 
+                        parameters: ParsingTaskParameters::Reddit(item), 
+                        social_network: SocialNetworkEnum::Reddit, 
+                        status: ParsingTaskStatus::New,
+                        seed: Some(TaskSeed::new(name.clone())),
+                        lease: None,
+                        retry: TaskRetry::default(),
+                        priority: 0,
+                        job: None,
+                        page: 0 }
+                    )
+            );
         }
 
-        return Ok(
-            parsing_tasks
-                .into_iter()
-                .map(|item| ParsingTask{ 
-                    _id: None, 
-                    execution_time: get_timestamp(), 
-                    action_type: item.to_string(), 
-                    parameters: ParsingTaskParameters::Reddit(item), 
-                    social_network: SocialNetworkEnum::Reddit, 
-                    status: ParsingTaskStatus::New }
-                )
-                .collect()
-        );
+        reddit_settings.set_priorities(&mut parsing_tasks);
+        return Ok(parsing_tasks);
     }
 
     fn prepare_accounts(&self, settings: SettingsPtr) -> Result<Vec<Account>, Box<dyn Error>> {
@@ -137,7 +159,22 @@ impl SocialNetwork for Reddit {
 
 
 impl Reddit {
-    async fn process_response(task: ParsingTask, account: AccountPtr, response: Result<Response, reqwest::Error>) {
+    async fn send_request(parsing_task: &ParsingTask, account: &AccountPtr) -> Result<Response, reqwest::Error> {
+        let reqwest_client = account.reqwest_client.clone().expect("Unauthorized account in Reddit parser");
+        let mut token = String::from("");
+        {
+            let rg_session = account.session.read().await;
+            token = rg_session.as_ref().expect("Unauthorized account in Reddit parser").token.clone();
+        }
+        return reqwest_client
+            .get(parsing_task.parameters.as_ref_reddit().to_url())
+            .bearer_auth(token)
+            .send()
+            .await;
+    }
+
+    //reauthorized is true when the request was repeated with a new token
+    async fn process_response(&self, task: ParsingTask, account: AccountPtr, response: Result<Response, reqwest::Error>, reauthorized: bool) {
         match response {
             Ok(response) => {
                 let (response_timestamp, millis_to_refresh, requests_limit) = Reddit::parse_limits_from_header(&response);
@@ -151,6 +188,38 @@ impl Reddit {
                                 response.json::<CommentPage>().await.inspect_err(|e| error!("unable to parse Post {} {}", response_url, e))
                             )
                         },
+                        RedditParsingTask::CommentThread { depth, .. } => {
+                            info!("Successfully recived comment thread page");
+                            ResponseBody::Comments(
+                                response.json::<CommentPage>().await
+                                    .map(|page| page.with_depth_offset(*depth))
+                                    .inspect_err(|e| error!("unable to parse CommentThread {} {}", response_url, e))
+                            )
+                        },
+                        RedditParsingTask::MoreComments { .. } => {
+                            info!("Successfully recived more comments page");
+                            ResponseBody::MoreComments(
+                                response.json::<MoreChildrenPage>().await.inspect_err(|e| error!("unable to parse MoreComments {} {}", response_url, e))
+                            )
+                        },
+                        RedditParsingTask::UserComments { .. } => {
+                            info!("Successfully recived user comments page");
+                            ResponseBody::UserComments(
+                                response.json::<UserCommentsPage>().await.inspect_err(|e| error!("unable to parse UserComments {} {}", response_url, e))
+                            )
+                        },
+                        RedditParsingTask::ThreadAbout { .. } => {
+                            info!("Successfully recived subreddit page");
+                            ResponseBody::Subreddit(
+                                response.json::<SubredditPage>().await.inspect_err(|e| error!("unable to parse ThreadAbout {} {}", response_url, e))
+                            )
+                        },
+                        RedditParsingTask::UserAbout { .. } => {
+                            info!("Successfully recived user page");
+                            ResponseBody::User(
+                                response.json::<UserPage>().await.inspect_err(|e| error!("unable to parse UserAbout {} {}", response_url, e))
+                            )
+                        },
                         _ =>  {
                             info!("Successfully recived thread page");
                             ResponseBody::Thread(
@@ -158,10 +227,29 @@ impl Reddit {
                             )
                         }
                     };
-                    if response_body.is_ok() {
-                        insert_tasks(&Reddit::spawn_new_tasks(&task, &response_body)).await;
-                        insert_with_replace(Self::get_entities(response_body)).await;
-                        update_tasks_with_status(vec![task._id.unwrap()], ParsingTaskStatus::Processed).await;
+                    if let Some(err) = response_body.get_error() {
+                        self.fail_task(&task, TaskFailure::Transient(format!("unable to parse response: {}", err))).await;
+                    } else {
+                        insert_tasks(&self.spawn_new_tasks(&task, &response_body).await).await;
+                        match response_body {
+                            ResponseBody::Subreddit(subreddit) => insert_community_snapshots(&vec![subreddit.unwrap().data.into()]).await,
+                            _ => {
+                                let entities = Self::get_entities(response_body);
+                                //trajectory is tracked for posts only, comments of every revisit are not snapshotted
+                                if let RedditParsingTask::Post { .. } = task.parameters.as_ref_reddit() {
+                                    insert_engagement_snapshots(&entities
+                                        .iter()
+                                        .filter(|entity| matches!(entity.entity_type, EntityType::Post))
+                                        .map(get_engagement_snapshot)
+                                        .collect()
+                                    ).await;
+                                }
+                                insert_with_replace(entities).await
+                            }
+                        }
+                        if !update_task_with_status(&task, ParsingTaskStatus::Processed).await {
+                            error!("lease of task {} was lost, it is processed by another worker", task.get_key());
+                        }
                     }
                     let mut wg_session = account.session.write().await;
                     let session = wg_session.as_mut().unwrap();
@@ -171,19 +259,35 @@ impl Reddit {
                 } else {
                     STATISTICS.increase_failed_parsing_tasks();
                     info!("Recived status: {}", response.status());
-                    update_tasks_with_status(vec![task._id.unwrap()], ParsingTaskStatus::New).await;
-                    if response.status() == StatusCode::FORBIDDEN {
-                        STATISTICS.increase_access_failed_parsing_tasks();
-                        account.auth().await;
-                    }
+                    let error = format!("received status {}", response.status());
+                    //banned subreddit, deleted post or user.
+                    //403 with a new token means private or quarantined content
+                    let failure = match response.status() {
+                        StatusCode::NOT_FOUND | StatusCode::GONE | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => TaskFailure::Permanent(error),
+                        StatusCode::FORBIDDEN if reauthorized => TaskFailure::Permanent(error),
+                        StatusCode::TOO_MANY_REQUESTS => TaskFailure::RateLimited(error),
+                        _ => TaskFailure::Transient(error)
+                    };
+                    self.fail_task(&task, failure).await;
                 }
             },
             Err(err) => {
                 info!("error {}", err);
+                STATISTICS.increase_failed_parsing_tasks();
+                self.fail_task(&task, TaskFailure::Transient(err.to_string())).await;
             },
         }
     }
 
+    async fn fail_task(&self, task: &ParsingTask, failure: TaskFailure) {
+        let retry_policy = self.retry_policy.read().unwrap().clone();
+        match fail_task(task, failure, &retry_policy).await {
+            Some(ParsingTaskStatus::New) => {},
+            Some(status) => error!("task {} moved to {}", task.get_key(), status),
+            None => error!("lease of task {} was lost, failure is dropped", task.get_key())
+        }
+    }
+
     fn parse_limits_from_header(response: &Response) -> (u64, u64, usize) {
         //Sun, 31 Jul 2022 00:01:30 GMT
         let timestamp: u64 = response
@@ -208,69 +312,319 @@ impl Reddit {
         return (timestamp, millis_to_refresh, requests_limit);
     }
 
-    fn spawn_new_tasks(parsing_task: &ParsingTask, response_body: &ResponseBody) -> Vec<ParsingTask> {
-        return match response_body {
-            ResponseBody::Thread(thread) => {
-                let thread = thread.as_ref().unwrap();
-                let after = thread.posts.data.after.clone();
-                let thread_task = match after {
-                    Some(after) => vec![
-                        ParsingTask {
+    fn get_seed_tasks(seed: &RedditSeed) -> Vec<RedditParsingTask> {
+        let mut tasks: Vec<RedditParsingTask> = Vec::new();
+        if let Some(thread) = &seed.thread {
+            //r/all and r/popular have listings only
+            if !is_aggregate_thread(thread) {
+                tasks.push(RedditParsingTask::ThreadAbout { thread: thread.clone() });
+            }
+            tasks.extend(Reddit::get_listing_tasks(thread, &seed.listings));
+        }
+        if let Some(user) = &seed.user {
+            tasks.extend(RedditParsingTask::get_all_user(user));
+        }
+        if let Some(search) = &seed.search {
+            tasks.push(RedditParsingTask::get_search(search));
+        }
+        return tasks;
+    }
+
+    fn get_listing_tasks(thread: &String, listings: &Option<Vec<RedditListing>>) -> Vec<RedditParsingTask> {
+        return match listings {
+            Some(listings) => RedditParsingTask::get_listings(thread, listings),
+            //r/all and r/popular mix posts of all subreddits, only hot is crawled unless listings are set
+            None if is_aggregate_thread(thread) => RedditParsingTask::get_listings(thread, &vec![RedditListing::Hot]),
+            None => RedditParsingTask::get_all(thread)
+        };
+    }
+
+    fn get_seed(&self, task_seed: &Option<TaskSeed>) -> Option<RedditSeed> {
+        return self.settings.read().unwrap().get_seed(task_seed).cloned();
+    }
+
+    async fn spawn_new_tasks(&self, parsing_task: &ParsingTask, response_body: &ResponseBody) -> Vec<ParsingTask> {
+        let mut new_tasks = match response_body {
+            ResponseBody::Subreddit(subreddit) => self.spawn_community_tasks(parsing_task, response_body, &subreddit.as_ref().unwrap().data).await,
+            _ => {
+                let mut new_tasks = self.spawn_response_tasks(parsing_task, response_body);
+                new_tasks.extend(self.spawn_revisit_task(parsing_task, response_body));
+                new_tasks.extend(self.spawn_poll_task(parsing_task, response_body));
+                new_tasks.extend(self.spawn_discovery_tasks(parsing_task, response_body).await);
+                self.apply_budget(parsing_task, response_body, new_tasks).await
+            }
+        };
+        self.settings.read().unwrap().set_priorities(&mut new_tasks);
+        return new_tasks;
+    }
+
+    fn spawn_revisit_task(&self, parsing_task: &ParsingTask, response_body: &ResponseBody) -> Option<ParsingTask> {
+        let (thread, id, update_number, last_observation) = match parsing_task.parameters.as_ref_reddit() {
+            RedditParsingTask::Post { thread, id, update_number, last_observation } => (thread, id, update_number, last_observation),
+            _ => return None
+        };
+        let post = match response_body {
+            ResponseBody::Comments(Ok(comments)) => &comments.post.data.children.first()?.data,
+            _ => return None
+        };
+        let now = get_timestamp();
+        let next_visit = self.settings
+            .read()
+            .unwrap()
+            .get_revisit_policy(thread, &parsing_task.seed)
+            .get_next_visit(post, last_observation, now)?;
+        return Some(ParsingTask { 
+            _id: None, 
+            execution_time: next_visit, 
+            parameters: ParsingTaskParameters::Reddit(
+                RedditParsingTask::Post { 
+                    thread: thread.clone(), 
+                    id: id.clone(), 
+                    update_number: update_number + 1,
+                    last_observation: Some(PostObservation::new(post, now, last_observation))
+                }
+            ), 
+            action_type: parsing_task.parameters.as_ref_reddit().to_string(),
+            social_network: SocialNetworkEnum::Reddit, 
+            status: ParsingTaskStatus::New,
+            seed: parsing_task.seed.clone(),
+            lease: None,
+            retry: TaskRetry::default(),
+            priority: 0,
+            job: parsing_task.job,
+            page: 0
+        });
+    }
+
+    //drops tasks exceeding the budget of the seed, next page task is dropped 
+    //when listing reached its limits and listing is polled again from the first page
+    async fn apply_budget(&self, parsing_task: &ParsingTask, response_body: &ResponseBody, mut new_tasks: Vec<ParsingTask>) -> Vec<ParsingTask> {
+        let (seed_name, budget) = match (&parsing_task.seed, self.get_seed(&parsing_task.seed).and_then(|seed| seed.budget)) {
+            (Some(task_seed), Some(budget)) => (task_seed.name.clone(), budget),
+            _ => return new_tasks
+        };
+        let is_next_page = |task: &ParsingTask| task.page > parsing_task.page;
+        let is_first_visit = |task: &ParsingTask| matches!(task.parameters.as_ref_reddit(), RedditParsingTask::Post { update_number: 0, .. });
+        let mut stop_pagination = budget.max_pages.is_some_and(|max_pages| parsing_task.page + 1 >= max_pages);
+
+        if let Some(max_revisits) = budget.max_revisits {
+            new_tasks.retain(|task| match task.parameters.as_ref_reddit() {
+                RedditParsingTask::Post { update_number, .. } => *update_number <= max_revisits,
+                _ => true
+            });
+        }
+        if let (Some(date_cutoff), ResponseBody::Thread(Ok(thread))) = (budget.date_cutoff, response_body) {
+            let old_posts: HashSet<&String> = thread.posts.data.children
+                .iter()
+                .filter(|item| item.data.timestamp.is_some_and(|timestamp| (timestamp as u64) < date_cutoff))
+                .map(|item| &item.data.id)
+                .collect();
+            stop_pagination |= old_posts.len() == thread.posts.data.children.len();
+            new_tasks.retain(|task| match task.parameters.as_ref_reddit() {
+                RedditParsingTask::Post { id: Some(id), update_number: 0, .. } => !old_posts.contains(id),
+                _ => true
+            });
+        }
+        if let Some(max_posts_per_day) = budget.max_posts_per_day {
+            let first_visits = new_tasks.iter().filter(|task| is_first_visit(task)).count() as u64;
+            if first_visits > 0 {
+                let spawned = count_seed_tasks_since(
+                    &seed_name, 
+                    doc! {"parameters.Reddit.Post.update_number": 0}, 
+                    get_timestamp() - Duration::days(1).num_milliseconds() as u64
+                ).await;
+                let mut remaining = max_posts_per_day.saturating_sub(spawned);
+                stop_pagination |= remaining < first_visits;
+                new_tasks.retain(|task| {
+                    if !is_first_visit(task) {
+                        return true;
+                    }
+                    if remaining == 0 {
+                        return false;
+                    }
+                    remaining -= 1;
+                    return true;
+                });
+            }
+        }
+        if stop_pagination && new_tasks.iter().any(is_next_page) {
+            info!("budget of seed {} is reached for {}", seed_name, parsing_task.get_key());
+            new_tasks.retain(|task| !is_next_page(task));
+            new_tasks.extend(self.get_poll_task(parsing_task));
+        }
+        return new_tasks;
+    }
+
+    //listing of a seed with schedule is started again from the first page after its last page
+    fn spawn_poll_task(&self, parsing_task: &ParsingTask, response_body: &ResponseBody) -> Option<ParsingTask> {
+        let after = match response_body {
+            ResponseBody::Thread(thread) => &thread.as_ref().unwrap().posts.data.after,
+            ResponseBody::UserComments(comments) => &comments.as_ref().unwrap().comments.data.after,
+            _ => return None
+        };
+        if after.is_some() {
+            return None;
+        }
+        return self.get_poll_task(parsing_task);
+    }
+
+    //first page of the listing at the next run of the seed schedule
+    fn get_poll_task(&self, parsing_task: &ParsingTask) -> Option<ParsingTask> {
+        //subreddits found by discovery are crawled once
+        if parsing_task.seed.as_ref().is_none_or(|seed| seed.depth > 0) {
+            return None;
+        }
+        let execution_time = self.get_seed(&parsing_task.seed)?.schedule?.get_next_run(get_timestamp())?;
+        return Some(ParsingTask {
+            _id: None,
+            execution_time,
+            parameters: ParsingTaskParameters::Reddit(parsing_task.parameters.as_ref_reddit().with_parameter(None)),
+            action_type: parsing_task.parameters.as_ref_reddit().to_string(),
+            social_network: SocialNetworkEnum::Reddit,
+            status: ParsingTaskStatus::New,
+            seed: parsing_task.seed.clone(),
+            lease: None,
+            retry: TaskRetry::default(),
+            priority: 0,
+            job: parsing_task.job,
+            page: 0
+        });
+    }
+
+    //discovered subreddits are crawled only after their subscribers count is checked
+    async fn spawn_community_tasks(&self, parsing_task: &ParsingTask, response_body: &ResponseBody, subreddit: &Subreddit) -> Vec<ParsingTask> {
+        let mut new_tasks: Vec<ParsingTask> = Vec::new();
+        if parsing_task.seed.as_ref().is_some_and(|task_seed| task_seed.depth > 0) {
+            let seed = self.get_seed(&parsing_task.seed);
+            let thread = parsing_task.parameters.as_ref_reddit().get_thread();
+            let min_subscribers = seed.as_ref()
+                .and_then(|seed| seed.discovery.as_ref())
+                .map_or(0, |discovery| discovery.min_subscribers);
+            if subreddit.subscribers.unwrap_or(0) < min_subscribers 
+                && update_discovered_community_status(&thread, SocialNetworkEnum::Reddit, DiscoveryStatus::Pending, DiscoveryStatus::Rejected).await {
+                info!("discovered {} rejected, not enough subscribers", thread);
+                return new_tasks;
+            }
+            if update_discovered_community_status(&thread, SocialNetworkEnum::Reddit, DiscoveryStatus::Pending, DiscoveryStatus::Active).await {
+                info!("discovered {} activated", thread);
+                new_tasks.extend(
+                    Reddit::get_listing_tasks(&thread, &seed.and_then(|seed| seed.listings))
+                        .into_iter()
+                        .map(|item| ParsingTask {
                             _id: None,
                             execution_time: get_timestamp(),
-                            parameters: ParsingTaskParameters::Reddit(
-                                parsing_task.parameters.as_ref_reddit().with_parameter(Some(after))
-                            ),
-                            action_type: parsing_task.parameters.as_ref_reddit().to_string(),
+                            action_type: item.to_string(),
+                            parameters: ParsingTaskParameters::Reddit(item),
                             social_network: SocialNetworkEnum::Reddit,
-                            status: ParsingTaskStatus::New
-                    }],
-                    None => vec![],
-                };
+                            status: ParsingTaskStatus::New,
+                            seed: parsing_task.seed.clone(),
+                            lease: None,
+                            retry: TaskRetry::default(),
+                            priority: 0,
+                            job: parsing_task.job,
+                            page: 0
+                        })
+                );
+            }
+        }
+        new_tasks.extend(self.spawn_response_tasks(parsing_task, response_body));
+        new_tasks.extend(self.spawn_discovery_tasks(parsing_task, response_body).await);
+        return new_tasks;
+    }
+
+    async fn spawn_discovery_tasks(&self, parsing_task: &ParsingTask, response_body: &ResponseBody) -> Vec<ParsingTask> {
+        let (task_seed, discovery) = match (&parsing_task.seed, self.get_seed(&parsing_task.seed).and_then(|seed| seed.discovery)) {
+            (Some(task_seed), Some(discovery)) if task_seed.depth < discovery.depth => (task_seed, discovery),
+            _ => return vec![]
+        };
+        let current_thread = normalize_thread(&parsing_task.parameters.as_ref_reddit().get_thread());
+        let candidates: Vec<String> = {
+            let settings = self.settings.read().unwrap();
+            get_candidates(response_body)
+                .into_iter()
+                .filter(|thread| *thread != current_thread && !settings.is_seed_thread(thread) && is_allowed(thread, &discovery))
+                .collect()
+        };
+
+        let mut new_tasks: Vec<ParsingTask> = Vec::new();
+        for thread in candidates {
+            let community = DiscoveredCommunity::new(thread.clone(), SocialNetworkEnum::Reddit, task_seed.name.clone(), task_seed.depth + 1);
+            if register_discovered_community(&community).await {
+                info!("discovered {} from seed {}", thread, task_seed.name);
+                new_tasks.push(ParsingTask {
+                    _id: None,
+                    execution_time: get_timestamp(),
+                    parameters: ParsingTaskParameters::Reddit(RedditParsingTask::ThreadAbout { thread }),
+                    action_type: parsing_task.parameters.as_ref_reddit().to_string(),
+                    social_network: SocialNetworkEnum::Reddit,
+                    status: ParsingTaskStatus::New,
+                    seed: Some(TaskSeed { name: task_seed.name.clone(), depth: task_seed.depth + 1 }),
+                    lease: None,
+                    retry: TaskRetry::default(),
+                    priority: 0,
+                    job: parsing_task.job,
+                    page: 0
+                });
+            }
+        }
+        return new_tasks;
+    }
+
+    fn spawn_response_tasks(&self, parsing_task: &ParsingTask, response_body: &ResponseBody) -> Vec<ParsingTask> {
+        return match response_body {
+            ResponseBody::Thread(thread) => {
+                let thread = thread.as_ref().unwrap();
                 let mut post_tasks = thread.posts.data.children.iter()
                     .map(|item| ParsingTask {
                         _id: None,
                         execution_time: get_timestamp(),// + Duration::hours(6).num_milliseconds() as u64,
                         parameters: ParsingTaskParameters::Reddit(
                             RedditParsingTask::Post {
-                                thread: parsing_task.parameters.as_ref_reddit().get_thread(), 
+                                thread: item.data.source.clone().unwrap_or_else(|| parsing_task.parameters.as_ref_reddit().get_thread()), 
                                 id: Some(item.data.id.clone()),
-                                update_number: 5
+                                update_number: 0,
+                                last_observation: None
                             }
                         ),
                         action_type: parsing_task.parameters.as_ref_reddit().to_string(),
                         social_network: SocialNetworkEnum::Reddit,
-                        status: ParsingTaskStatus::New
+                        status: ParsingTaskStatus::New,
+                        seed: parsing_task.seed.clone(),
+                        lease: None,
+                        retry: TaskRetry::default(),
+                        priority: 0,
+                        job: parsing_task.job,
+                        page: 0
                     }).collect::<Vec<_>>();
-                post_tasks.extend(thread_task);
+                post_tasks.extend(Reddit::spawn_next_page_task(parsing_task, &thread.posts.data.after));
                 return post_tasks;
             },
-            ResponseBody::Comments(comments) => {
-                match &parsing_task.parameters.as_ref_reddit() {
-                    RedditParsingTask::Post { thread, id, update_number } => {
-                        let mut new_task = vec![];
-                        if *update_number <= 2 {
-                            new_task = vec![
-                                ParsingTask { 
-                                    _id: None, 
-                                    execution_time: get_timestamp() + Duration::hours(1).num_milliseconds() as u64, 
-                                    parameters: ParsingTaskParameters::Reddit(
-                                        RedditParsingTask::Post { 
-                                            thread: thread.clone(), 
-                                            id: Some(id.as_ref().unwrap().clone()), 
-                                            update_number: update_number + 1 
-                                        }
-                                    ), 
-                                    action_type: parsing_task.parameters.as_ref_reddit().to_string(),
-                                    social_network: SocialNetworkEnum::Reddit, 
-                                    status: ParsingTaskStatus::New 
-                                }
-                            ]
-                        }
-                        return new_task
-                    },
-                    _ => {vec![]}
+            ResponseBody::UserComments(comments) => {
+                return Reddit::spawn_next_page_task(parsing_task, &comments.as_ref().unwrap().comments.data.after);
+            },
+            ResponseBody::User(_) => vec![],
+            ResponseBody::Subreddit(_) => vec![
+                ParsingTask {
+                    _id: None,
+                    execution_time: get_timestamp() + Duration::hours(self.settings.read().unwrap().community_snapshot_interval_hours as i64).num_milliseconds() as u64,
+                    parameters: parsing_task.parameters.clone(),
+                    action_type: parsing_task.parameters.as_ref_reddit().to_string(),
+                    social_network: SocialNetworkEnum::Reddit,
+                    status: ParsingTaskStatus::New,
+                    seed: parsing_task.seed.clone(),
+                    lease: None,
+                    retry: TaskRetry::default(),
+                    priority: 0,
+                    job: parsing_task.job,
+                    page: 0
                 }
+            ],
+            ResponseBody::Comments(comments) => {
+                let comments = comments.as_ref().unwrap();
+                return Reddit::spawn_more_comments_tasks(parsing_task, comments.get_more_comments());
+            },
+            ResponseBody::MoreComments(comments) => {
+                return Reddit::spawn_more_comments_tasks(parsing_task, comments.as_ref().unwrap().get_more_comments());
             },
         };
     }
@@ -282,12 +636,96 @@ impl Reddit {
                 thread.unwrap().posts.data.children.into_iter().for_each(|item| entities.push(item.data.into()));
             },
             ResponseBody::Comments(comments) => {
-                let mut comments = comments.unwrap();
-                comments.comments.data.children.into_iter().for_each(|item| entities.push(item.data.into()));
+                let comments = comments.unwrap();
+                comments.post.data.children.iter().for_each(|item| entities.push(item.data.clone().into()));
+                comments.get_comments().into_iter().for_each(|item| entities.push(item.into()));
+            },
+            ResponseBody::MoreComments(comments) => {
+                comments.unwrap().get_comments().into_iter().for_each(|item| entities.push(item.into()));
+            },
+            ResponseBody::UserComments(comments) => {
+                comments.unwrap().comments.data.children.into_iter().for_each(|item| entities.push(item.data.into()));
+            },
+            ResponseBody::User(user) => {
+                entities.push(user.unwrap().data.into());
             },
+            ResponseBody::Subreddit(_) => {},
         }
         return entities;
     }
+
+    fn spawn_next_page_task(parsing_task: &ParsingTask, after: &Option<String>) -> Vec<ParsingTask> {
+        return match after {
+            Some(after) => vec![
+                ParsingTask {
+                    _id: None,
+                    execution_time: get_timestamp(),
+                    parameters: ParsingTaskParameters::Reddit(
+                        parsing_task.parameters.as_ref_reddit().with_parameter(Some(after.clone()))
+                    ),
+                    action_type: parsing_task.parameters.as_ref_reddit().to_string(),
+                    social_network: SocialNetworkEnum::Reddit,
+                    status: ParsingTaskStatus::New,
+                    seed: parsing_task.seed.clone(),
+                    lease: None,
+                    retry: TaskRetry::default(),
+                    priority: 0,
+                    job: parsing_task.job,
+                    page: parsing_task.page + 1
+            }],
+            None => vec![],
+        };
+    }
+
+    fn spawn_more_comments_tasks(parsing_task: &ParsingTask, more_comments: Vec<&MoreComments>) -> Vec<ParsingTask> {
+        let (thread, id) = match parsing_task.parameters.as_ref_reddit() {
+            RedditParsingTask::Post { thread, id: Some(id), .. } |
+            RedditParsingTask::MoreComments { thread, id, .. } |
+            RedditParsingTask::CommentThread { thread, id, .. } => (thread.clone(), id.clone()),
+            _ => return vec![]
+        };
+
+        let mut collapsed_children: Vec<String> = Vec::new();
+        let mut reddit_tasks: Vec<RedditParsingTask> = Vec::new();
+        for more in more_comments {
+            if more.children.is_empty() {
+                //"continue this thread" stub, the branch is fetched starting from its parent comment
+                reddit_tasks.push(RedditParsingTask::CommentThread { 
+                    thread: thread.clone(), 
+                    id: id.clone(), 
+                    comment: more.parent_id.clone(),
+                    depth: more.depth.unwrap_or(1).saturating_sub(1)
+                });
+            } else {
+                collapsed_children.extend(more.children.iter().cloned());
+            }
+        }
+        collapsed_children
+            .chunks(MORE_CHILDREN_LIMIT)
+            .for_each(|children| reddit_tasks.push(RedditParsingTask::MoreComments { 
+                thread: thread.clone(), 
+                id: id.clone(), 
+                children: children.to_vec() 
+            }));
+
+        return reddit_tasks
+            .into_iter()
+            .map(|item| ParsingTask {
+                _id: None,
+                execution_time: get_timestamp(),
+                action_type: item.to_string(),
+                parameters: ParsingTaskParameters::Reddit(item),
+                social_network: SocialNetworkEnum::Reddit,
+                status: ParsingTaskStatus::New,
+                seed: parsing_task.seed.clone(),
+                lease: None,
+                retry: TaskRetry::default(),
+                priority: 0,
+                job: parsing_task.job,
+                page: 0
+            })
+            .collect();
+    }
 }
 
 
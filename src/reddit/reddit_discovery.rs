@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{
+    reddit_settings::DiscoverySettings, 
+    data_types::reddit_response_body::ResponseBody
+};
+
+lazy_static! {
+    static ref SUBREDDIT_MENTION: Regex = Regex::new(r"(?i)\br/([a-z0-9][a-z0-9_]{2,20})\b").unwrap();
+}
+
+//listings mixing posts of all subreddits, they have no about page and are not discovered
+const AGGREGATE_THREADS: [&str; 2] = ["r/all", "r/popular"];
+
+pub fn normalize_thread(thread: &str) -> String {
+    let thread = thread.trim().trim_start_matches('/').to_lowercase();
+    return match thread.starts_with("r/") {
+        true => thread,
+        false => format!("r/{}", thread)
+    };
+}
+
+pub fn is_aggregate_thread(thread: &str) -> bool {
+    return AGGREGATE_THREADS.contains(&normalize_thread(thread).as_str());
+}
+
+pub fn find_mentions(text: &str) -> Vec<String> {
+    return SUBREDDIT_MENTION
+        .captures_iter(text)
+        .map(|captures| normalize_thread(&captures[1]))
+        .collect();
+}
+
+//subreddits referenced by crossposts, mentions in posts and comments and subreddit sidebar
+pub fn get_candidates(response_body: &ResponseBody) -> HashSet<String> {
+    let mut texts: Vec<&str> = Vec::new();
+    let mut candidates: HashSet<String> = HashSet::new();
+    match response_body {
+        ResponseBody::Thread(Ok(thread)) => {
+            for post in thread.posts.data.children.iter() {
+                texts.push(&post.data.title);
+                texts.extend(post.data.self_text.as_deref());
+                candidates.extend(post.data.get_crosspost_sources().into_iter().map(|source| normalize_thread(&source)));
+            }
+        },
+        ResponseBody::Comments(Ok(comments)) => {
+            for post in comments.post.data.children.iter() {
+                texts.extend(post.data.self_text.as_deref());
+            }
+            let (comments, _) = comments.walk();
+            texts.extend(comments.into_iter().filter_map(|comment| comment.body.as_deref()));
+        },
+        ResponseBody::MoreComments(Ok(comments)) => {
+            let (comments, _) = comments.walk();
+            texts.extend(comments.into_iter().filter_map(|comment| comment.body.as_deref()));
+        },
+        ResponseBody::UserComments(Ok(comments)) => {
+            texts.extend(comments.comments.data.children.iter().filter_map(|comment| comment.data.body.as_deref()));
+        },
+        ResponseBody::Subreddit(Ok(subreddit)) => {
+            texts.extend(subreddit.data.description.as_deref());
+            texts.extend(subreddit.data.public_description.as_deref());
+        },
+        _ => {}
+    }
+    texts.into_iter().for_each(|text| candidates.extend(find_mentions(text)));
+    return candidates;
+}
+
+pub fn is_allowed(thread: &str, discovery: &DiscoverySettings) -> bool {
+    let matches = |list: &Vec<String>| list.iter().any(|item| normalize_thread(item) == thread);
+    return !is_aggregate_thread(thread) && (discovery.allow.is_empty() || matches(&discovery.allow)) && !matches(&discovery.deny);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mentions_are_normalized() {
+        assert_eq!(
+            find_mentions("see r/Rust, /r/programming and R/learn_rust."), 
+            vec!["r/rust", "r/programming", "r/learn_rust"]
+        );
+    }
+
+    #[test]
+    fn mentions_in_links_are_found() {
+        assert_eq!(find_mentions("https://www.reddit.com/r/rust/comments/abc/title/"), vec!["r/rust"]);
+    }
+
+    #[test]
+    fn invalid_names_are_skipped() {
+        //too short, starts with underscore, too long
+        assert!(find_mentions("r/ab r/_rust r/abcdefghijklmnopqrstuvw").is_empty());
+    }
+
+    #[test]
+    fn mentions_inside_words_are_skipped() {
+        assert!(find_mentions("four/rust and user/rust").is_empty());
+    }
+
+    #[test]
+    fn aggregate_threads() {
+        assert!(is_aggregate_thread("r/all"));
+        assert!(is_aggregate_thread("/r/Popular"));
+        assert!(is_aggregate_thread("all"));
+        assert!(!is_aggregate_thread("r/allergies"));
+    }
+}
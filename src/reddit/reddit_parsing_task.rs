@@ -1,6 +1,8 @@
 use serde::{Serialize, Deserialize};
 use strum::{EnumIter, EnumString, Display};
 
+use super::{reddit_settings::SearchSettings, reddit_revisit::{PostObservation, GAINING_VELOCITY}};
+
 //rework this with macro
 
 #[derive(Serialize, Deserialize, Display, Debug, Clone, Eq, Hash, PartialEq, EnumIter, EnumString)]
@@ -10,7 +12,43 @@ pub enum RedditParsingTask {
     ThreadTopYearHistory{thread: String, after: Option<String>},
     ThreadTopMonthHistory{thread: String, after: Option<String>},
     ThreadTopWeekHistory{thread: String, after: Option<String>},
-    Post{thread: String, id: Option<String>, update_number: u64}
+    ThreadTopDayHistory{thread: String, after: Option<String>},
+    ThreadTopHourHistory{thread: String, after: Option<String>},
+    ThreadHot{thread: String, after: Option<String>},
+    ThreadRising{thread: String, after: Option<String>},
+    ThreadControversial{thread: String, time: String, after: Option<String>},
+    ThreadAbout{thread: String},
+    //update_number is the number of visits made before this one
+    Post{thread: String, id: Option<String>, update_number: u64, #[serde(default)] last_observation: Option<PostObservation>},
+    MoreComments{thread: String, id: String, children: Vec<String>},
+    //depth is the depth of comment in the whole post, reddit counts depth of thread from the comment
+    CommentThread{thread: String, id: String, comment: String, #[serde(default)] depth: u64},
+    UserSubmitted{user: String, after: Option<String>},
+    UserComments{user: String, after: Option<String>},
+    UserAbout{user: String},
+    //thread is None for site-wide search
+    Search{thread: Option<String>, query: String, sort: Option<String>, time: Option<String>, restrict_sr: bool, after: Option<String>}
+}
+
+//listing names accepted in the "listings" array of a thread seed
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, Hash, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RedditListing {
+    New,
+    Hot,
+    Rising,
+    TopHour,
+    TopDay,
+    TopWeek,
+    TopMonth,
+    TopYear,
+    TopAll,
+    ControversialHour,
+    ControversialDay,
+    ControversialWeek,
+    ControversialMonth,
+    ControversialYear,
+    ControversialAll
 }
 
 impl RedditParsingTask {
@@ -25,6 +63,51 @@ impl RedditParsingTask {
         ];
     }
 
+    pub fn get_listings(thread: &String, listings: &Vec<RedditListing>) -> Vec<RedditParsingTask> {
+        let thread = thread.clone();
+        let controversial = |time: &str| RedditParsingTask::ThreadControversial { thread: thread.clone(), time: time.to_string(), after: None };
+        return listings
+            .iter()
+            .map(|listing| match listing {
+                RedditListing::New => RedditParsingTask::ThreadNew { thread: thread.clone(), after: None },
+                RedditListing::Hot => RedditParsingTask::ThreadHot { thread: thread.clone(), after: None },
+                RedditListing::Rising => RedditParsingTask::ThreadRising { thread: thread.clone(), after: None },
+                RedditListing::TopHour => RedditParsingTask::ThreadTopHourHistory { thread: thread.clone(), after: None },
+                RedditListing::TopDay => RedditParsingTask::ThreadTopDayHistory { thread: thread.clone(), after: None },
+                RedditListing::TopWeek => RedditParsingTask::ThreadTopWeekHistory { thread: thread.clone(), after: None },
+                RedditListing::TopMonth => RedditParsingTask::ThreadTopMonthHistory { thread: thread.clone(), after: None },
+                RedditListing::TopYear => RedditParsingTask::ThreadTopYearHistory { thread: thread.clone(), after: None },
+                RedditListing::TopAll => RedditParsingTask::ThreadTopAllTimeHistory { thread: thread.clone(), after: None },
+                RedditListing::ControversialHour => controversial("hour"),
+                RedditListing::ControversialDay => controversial("day"),
+                RedditListing::ControversialWeek => controversial("week"),
+                RedditListing::ControversialMonth => controversial("month"),
+                RedditListing::ControversialYear => controversial("year"),
+                RedditListing::ControversialAll => controversial("all"),
+            })
+            .collect();
+    }
+
+    pub fn get_all_user(user: &String) -> Vec<RedditParsingTask> {
+        let user = user.strip_prefix("u/").unwrap_or(user).to_string();
+        return vec![
+            RedditParsingTask::UserAbout { user: user.clone() },
+            RedditParsingTask::UserSubmitted { user: user.clone(), after: None },
+            RedditParsingTask::UserComments { user: user.clone(), after: None }
+        ];
+    }
+
+    pub fn get_search(search: &SearchSettings) -> RedditParsingTask {
+        return RedditParsingTask::Search { 
+            restrict_sr: search.restrict_sr.unwrap_or(search.thread.is_some()),
+            thread: search.thread.clone(), 
+            query: search.query.clone(), 
+            sort: search.sort.clone(), 
+            time: search.time.clone(), 
+            after: None 
+        };
+    }
+
     pub fn to_url(&self) -> String {
         return match self {
             RedditParsingTask::Post{thread, id, .. } => 
@@ -33,6 +116,41 @@ impl RedditParsingTask {
                     thread,
                     ID = id.as_ref().unwrap_or(&"".to_string()).strip_prefix("t3_").unwrap_or(&"")
                 ),
+            RedditParsingTask::MoreComments{ id, children, .. } => 
+                format!(
+                    "https://oauth.reddit.com/api/morechildren?api_type=json&link_id={}&children={}&sort=top",
+                    id,
+                    children.join(",")
+                ),
+            RedditParsingTask::CommentThread{ thread, id, comment, .. } => 
+                reqwest::Url::parse_with_params(
+                    &format!(
+                        "https://oauth.reddit.com/{}/comments/{}/_/{}.json",
+                        thread,
+                        id.strip_prefix("t3_").unwrap_or(id),
+                        comment.strip_prefix("t1_").unwrap_or(comment)
+                    ),
+                    [("sort", "top")]
+                ).expect("unable to build comment thread url").to_string(),
+            RedditParsingTask::Search { thread, query, sort, time, restrict_sr, after } => {
+                let mut parameters = vec![
+                    ("q", query.clone()), 
+                    ("restrict_sr", restrict_sr.to_string())
+                ];
+                if let Some(sort) = sort {
+                    parameters.push(("sort", sort.clone()));
+                }
+                if let Some(time) = time {
+                    parameters.push(("t", time.clone()));
+                }
+                if let Some(after) = after {
+                    parameters.extend([("after", after.clone()), ("limit", "100".to_string())]);
+                }
+                reqwest::Url::parse_with_params(
+                    &format!("https://oauth.reddit.com/{}search.json", thread.as_ref().map_or("".to_string(), |thread| format!("{}/", thread))),
+                    parameters
+                ).expect("unable to build search url").to_string()
+            },
             _ => format!(
                 "https://oauth.reddit.com/{}/{}", 
                 self.get_thread(),
@@ -47,12 +165,24 @@ impl RedditParsingTask {
             .as_ref()
             .map_or("".to_string(), |after| format!("&after={}&limit=100", &after));
         let base_rq = match self {
-            RedditParsingTask::ThreadNew { .. } => "new.json?",
-            RedditParsingTask::ThreadTopAllTimeHistory { .. } => "top.json?t=all",
-            RedditParsingTask::ThreadTopYearHistory { .. } => "top.json?t=year",
-            RedditParsingTask::ThreadTopMonthHistory { .. } => "top.json?t=month",
-            RedditParsingTask::ThreadTopWeekHistory { .. } => "top.json?t=week",
-            RedditParsingTask::Post { .. } => panic!("this branch should not be reached"),
+            RedditParsingTask::ThreadNew { .. } => "new.json?".to_string(),
+            RedditParsingTask::ThreadTopAllTimeHistory { .. } => "top.json?t=all".to_string(),
+            RedditParsingTask::ThreadTopYearHistory { .. } => "top.json?t=year".to_string(),
+            RedditParsingTask::ThreadTopMonthHistory { .. } => "top.json?t=month".to_string(),
+            RedditParsingTask::ThreadTopWeekHistory { .. } => "top.json?t=week".to_string(),
+            RedditParsingTask::ThreadTopDayHistory { .. } => "top.json?t=day".to_string(),
+            RedditParsingTask::ThreadTopHourHistory { .. } => "top.json?t=hour".to_string(),
+            RedditParsingTask::ThreadHot { .. } => "hot.json?".to_string(),
+            RedditParsingTask::ThreadRising { .. } => "rising.json?".to_string(),
+            RedditParsingTask::ThreadControversial { time, .. } => format!("controversial.json?t={}", time),
+            RedditParsingTask::UserSubmitted { .. } => "submitted.json?".to_string(),
+            RedditParsingTask::UserComments { .. } => "comments.json?".to_string(),
+            RedditParsingTask::ThreadAbout { .. } |
+            RedditParsingTask::UserAbout { .. } => "about.json?".to_string(),
+            RedditParsingTask::Post { .. } |
+            RedditParsingTask::MoreComments { .. } |
+            RedditParsingTask::CommentThread { .. } |
+            RedditParsingTask::Search { .. } => panic!("this branch should not be reached"),
         };
         return format!("{}{}", base_rq, filter_rq);
     }
@@ -63,8 +193,20 @@ impl RedditParsingTask {
             RedditParsingTask::ThreadTopAllTimeHistory{  thread: _, after } |
             RedditParsingTask::ThreadTopYearHistory{  thread: _, after } |
             RedditParsingTask::ThreadTopMonthHistory{  thread: _, after } |
-            RedditParsingTask::ThreadTopWeekHistory {  thread: _, after } => after.clone(),
-            RedditParsingTask::Post { .. } => panic!("this branch should not be reached"),
+            RedditParsingTask::ThreadTopWeekHistory {  thread: _, after } |
+            RedditParsingTask::ThreadTopDayHistory {  thread: _, after } |
+            RedditParsingTask::ThreadTopHourHistory {  thread: _, after } |
+            RedditParsingTask::ThreadHot {  thread: _, after } |
+            RedditParsingTask::ThreadRising {  thread: _, after } |
+            RedditParsingTask::ThreadControversial {  thread: _, time: _, after } |
+            RedditParsingTask::UserSubmitted { user: _, after } |
+            RedditParsingTask::UserComments { user: _, after } |
+            RedditParsingTask::Search { after, .. } => after.clone(),
+            RedditParsingTask::ThreadAbout { .. } |
+            RedditParsingTask::UserAbout { .. } => None,
+            RedditParsingTask::Post { .. } |
+            RedditParsingTask::MoreComments { .. } |
+            RedditParsingTask::CommentThread { .. } => panic!("this branch should not be reached"),
         }
     }
 
@@ -75,10 +217,62 @@ impl RedditParsingTask {
             RedditParsingTask::ThreadTopYearHistory { thread, .. } |
             RedditParsingTask::ThreadTopMonthHistory { thread, .. } |
             RedditParsingTask::ThreadTopWeekHistory { thread, .. } |
-            RedditParsingTask::Post { thread, .. } => thread.clone(),
+            RedditParsingTask::ThreadTopDayHistory { thread, .. } |
+            RedditParsingTask::ThreadTopHourHistory { thread, .. } |
+            RedditParsingTask::ThreadHot { thread, .. } |
+            RedditParsingTask::ThreadRising { thread, .. } |
+            RedditParsingTask::ThreadControversial { thread, .. } |
+            RedditParsingTask::ThreadAbout { thread } |
+            RedditParsingTask::Post { thread, .. } |
+            RedditParsingTask::MoreComments { thread, .. } |
+            RedditParsingTask::CommentThread { thread, .. } => thread.clone(),
+            RedditParsingTask::UserSubmitted { user, .. } |
+            RedditParsingTask::UserComments { user, .. } |
+            RedditParsingTask::UserAbout { user } => format!("user/{}", user),
+            RedditParsingTask::Search { thread, .. } => thread.clone().unwrap_or_default(),
+        }
+    }
+
+    //fresh content goes before backfill, settings may override these values
+    pub fn get_default_priority(&self) -> i64 {
+        return match self {
+            RedditParsingTask::ThreadNew { .. } => 10,
+            RedditParsingTask::ThreadHot { .. } |
+            RedditParsingTask::ThreadRising { .. } => 8,
+            RedditParsingTask::Post { update_number: 0, .. } |
+            RedditParsingTask::ThreadAbout { .. } => 6,
+            //revisits of posts gaining score and comments go before revisits of settled ones
+            RedditParsingTask::Post { last_observation: Some(observation), .. } if observation.velocity >= GAINING_VELOCITY => 5,
+            RedditParsingTask::Post { .. } => 3,
+            RedditParsingTask::MoreComments { .. } |
+            RedditParsingTask::CommentThread { .. } |
+            RedditParsingTask::Search { .. } => 5,
+            RedditParsingTask::UserSubmitted { .. } |
+            RedditParsingTask::UserComments { .. } |
+            RedditParsingTask::UserAbout { .. } |
+            RedditParsingTask::ThreadTopHourHistory { .. } |
+            RedditParsingTask::ThreadTopDayHistory { .. } => 3,
+            RedditParsingTask::ThreadTopWeekHistory { .. } |
+            RedditParsingTask::ThreadTopMonthHistory { .. } |
+            RedditParsingTask::ThreadControversial { .. } => 2,
+            RedditParsingTask::ThreadTopYearHistory { .. } => 1,
+            RedditParsingTask::ThreadTopAllTimeHistory { .. } => 0,
         }
     }
 
+    //identifying fields of the task, all visits of a post have the same key
+    pub fn get_key(&self) -> String {
+        return match self {
+            RedditParsingTask::Post { thread, id, .. } => format!("Post:{}:{}", thread, id.clone().unwrap_or_default()),
+            _ => serde_json::to_string(self).unwrap()
+        };
+    }
+
+    //first visit of a post is done once, other tasks may be queued again after they are processed
+    pub fn is_one_shot(&self) -> bool {
+        return matches!(self, RedditParsingTask::Post { update_number: 0, .. });
+    }
+
     pub fn with_parameter(&self, parameter: Option<String>) -> RedditParsingTask {
         return match self {
             RedditParsingTask::ThreadNew { thread, after: _ } => RedditParsingTask::ThreadNew { thread: thread.clone(), after: parameter },
@@ -86,7 +280,31 @@ impl RedditParsingTask {
             RedditParsingTask::ThreadTopYearHistory { thread, after: _ } => RedditParsingTask::ThreadTopYearHistory { thread: thread.clone(), after: parameter },
             RedditParsingTask::ThreadTopMonthHistory { thread, after: _ } => RedditParsingTask::ThreadTopMonthHistory { thread: thread.clone(), after: parameter },
             RedditParsingTask::ThreadTopWeekHistory { thread, after: _ } => RedditParsingTask::ThreadTopWeekHistory { thread: thread.clone(), after: parameter },
-            RedditParsingTask::Post { thread, id, update_number } => RedditParsingTask::Post { thread: thread.clone(), id: id.clone(), update_number: *update_number },
+            RedditParsingTask::ThreadTopDayHistory { thread, after: _ } => RedditParsingTask::ThreadTopDayHistory { thread: thread.clone(), after: parameter },
+            RedditParsingTask::ThreadTopHourHistory { thread, after: _ } => RedditParsingTask::ThreadTopHourHistory { thread: thread.clone(), after: parameter },
+            RedditParsingTask::ThreadHot { thread, after: _ } => RedditParsingTask::ThreadHot { thread: thread.clone(), after: parameter },
+            RedditParsingTask::ThreadRising { thread, after: _ } => RedditParsingTask::ThreadRising { thread: thread.clone(), after: parameter },
+            RedditParsingTask::ThreadControversial { thread, time, after: _ } => RedditParsingTask::ThreadControversial { thread: thread.clone(), time: time.clone(), after: parameter },
+            RedditParsingTask::Post { thread, id, update_number, last_observation } => RedditParsingTask::Post { 
+                thread: thread.clone(), 
+                id: id.clone(), 
+                update_number: *update_number, 
+                last_observation: last_observation.clone() 
+            },
+            RedditParsingTask::UserSubmitted { user, after: _ } => RedditParsingTask::UserSubmitted { user: user.clone(), after: parameter },
+            RedditParsingTask::UserComments { user, after: _ } => RedditParsingTask::UserComments { user: user.clone(), after: parameter },
+            RedditParsingTask::Search { thread, query, sort, time, restrict_sr, after: _ } => RedditParsingTask::Search { 
+                thread: thread.clone(), 
+                query: query.clone(), 
+                sort: sort.clone(), 
+                time: time.clone(), 
+                restrict_sr: *restrict_sr, 
+                after: parameter 
+            },
+            RedditParsingTask::MoreComments { .. } |
+            RedditParsingTask::CommentThread { .. } |
+            RedditParsingTask::ThreadAbout { .. } |
+            RedditParsingTask::UserAbout { .. } => self.clone(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,151 @@
+use serde::{Serialize, Deserialize};
+
+use super::data_types::reddit_post::Post;
+
+const MINUTE: u64 = 60 * 1000;
+const HOUR: u64 = 60 * MINUTE;
+//score and comments change per hour at which revisited post is still gaining engagement
+pub const GAINING_VELOCITY: u64 = 10;
+
+//post engagement seen on the previous visit
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, Hash, PartialEq)]
+pub struct PostObservation {
+    pub timestamp: u64,
+    pub score: i64,
+    pub comments: u64,
+    //score and comments change per hour since the visit before
+    #[serde(default)]
+    pub velocity: u64
+}
+
+impl PostObservation {
+    pub fn new(post: &Post, timestamp: u64, previous: &Option<PostObservation>) -> PostObservation {
+        return PostObservation { 
+            timestamp, 
+            score: post.ups, 
+            comments: post.num_comments.unwrap_or(0),
+            velocity: previous
+                .as_ref()
+                .and_then(|previous| previous.get_velocity(post, timestamp))
+                .unwrap_or(0.0) as u64
+        };
+    }
+
+    pub fn get_velocity(&self, post: &Post, now: u64) -> Option<f64> {
+        if now <= self.timestamp {
+            return None;
+        }
+        let hours = (now - self.timestamp) as f64 / HOUR as f64;
+        let change = (post.ups - self.score).abs() as f64 
+            + post.num_comments.unwrap_or(0).saturating_sub(self.comments) as f64;
+        return Some(change / hours);
+    }
+}
+
+//revisit interval is inversely proportional to score and comments velocity
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RevisitPolicy {
+    pub min_interval_minutes: u64,
+    pub max_interval_minutes: u64,
+    //posts older than that are not revisited
+    pub max_age_hours: u64,
+    //score and comments change per hour at which post is revisited with min interval
+    pub hot_velocity: f64
+}
+
+impl Default for RevisitPolicy {
+    fn default() -> Self {
+        Self { 
+            min_interval_minutes: 15, 
+            max_interval_minutes: 12 * 60, 
+            max_age_hours: 72, 
+            hot_velocity: 100.0 
+        }
+    }
+}
+
+impl RevisitPolicy {
+
+    pub fn get_next_visit(&self, post: &Post, previous: &Option<PostObservation>, now: u64) -> Option<u64> {
+        let created = post.timestamp.map_or(now, |timestamp| timestamp as u64 * 1000);
+        if now.saturating_sub(created) > self.max_age_hours * HOUR {
+            return None;
+        }
+        let interval_minutes = match previous.as_ref().and_then(|previous| previous.get_velocity(post, now)) {
+            Some(velocity) => self.min_interval_minutes as f64 * self.hot_velocity / velocity.max(f64::EPSILON),
+            None => self.min_interval_minutes as f64
+        };
+        let interval_minutes = interval_minutes.clamp(
+            self.min_interval_minutes as f64, 
+            self.max_interval_minutes.max(self.min_interval_minutes) as f64
+        ) as u64;
+        return Some(now + interval_minutes * MINUTE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const NOW: u64 = 100 * HOUR;
+
+    fn get_post(age: u64, score: i64, comments: u64) -> Post {
+        return serde_json::from_value(json!({
+            "name": "t3_post",
+            "created": ((NOW - age) / 1000) as f64,
+            "title": "post",
+            "ups": score,
+            "num_comments": comments
+        })).unwrap();
+    }
+
+    fn get_observation(hours_ago: u64, score: i64, comments: u64) -> Option<PostObservation> {
+        return Some(PostObservation { timestamp: NOW - hours_ago * HOUR, score, comments, velocity: 0 });
+    }
+
+    #[test]
+    fn first_revisit_uses_min_interval() {
+        let policy = RevisitPolicy::default();
+        let next_visit = policy.get_next_visit(&get_post(HOUR, 10, 1), &None, NOW);
+        assert_eq!(next_visit, Some(NOW + policy.min_interval_minutes * MINUTE));
+    }
+
+    #[test]
+    fn hot_post_interval_is_clamped_to_min() {
+        let policy = RevisitPolicy::default();
+        let next_visit = policy.get_next_visit(&get_post(HOUR, 100_000, 0), &get_observation(1, 0, 0), NOW);
+        assert_eq!(next_visit, Some(NOW + policy.min_interval_minutes * MINUTE));
+    }
+
+    #[test]
+    fn settled_post_interval_is_clamped_to_max() {
+        let policy = RevisitPolicy::default();
+        let next_visit = policy.get_next_visit(&get_post(HOUR, 10, 1), &get_observation(1, 10, 1), NOW);
+        assert_eq!(next_visit, Some(NOW + policy.max_interval_minutes * MINUTE));
+    }
+
+    #[test]
+    fn interval_is_proportional_between_bounds() {
+        let policy = RevisitPolicy::default();
+        //half of hot velocity doubles min interval
+        let next_visit = policy.get_next_visit(&get_post(HOUR, 50, 0), &get_observation(1, 0, 0), NOW);
+        assert_eq!(next_visit, Some(NOW + 2 * policy.min_interval_minutes * MINUTE));
+    }
+
+    #[test]
+    fn max_interval_below_min_uses_min() {
+        let policy = RevisitPolicy { min_interval_minutes: 60, max_interval_minutes: 10, ..RevisitPolicy::default() };
+        let next_visit = policy.get_next_visit(&get_post(HOUR, 10, 1), &get_observation(1, 10, 1), NOW);
+        assert_eq!(next_visit, Some(NOW + 60 * MINUTE));
+    }
+
+    #[test]
+    fn old_post_is_not_revisited() {
+        let policy = RevisitPolicy::default();
+        let age = (policy.max_age_hours + 1) * HOUR;
+        assert_eq!(policy.get_next_visit(&get_post(age, 10, 1), &None, NOW), None);
+    }
+}
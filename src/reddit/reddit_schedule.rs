@@ -0,0 +1,47 @@
+use std::str::FromStr;
+
+use chrono::{TimeZone, Utc};
+use cron::Schedule;
+use serde::{Serialize, Deserialize, Deserializer, Serializer};
+
+const MINUTE: u64 = 60 * 1000;
+
+//recurring crawl of seed listings, listing is started again from the first page
+//when it has no more pages. cron expression has seconds field: "0 */30 * * * *"
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PollSchedule {
+    pub interval_minutes: Option<u64>,
+    #[serde(default, serialize_with = "serialize_cron", deserialize_with = "deserialize_cron")]
+    pub cron: Option<Schedule>
+}
+
+impl PollSchedule {
+
+    //cron expression takes precedence over interval
+    pub fn get_next_run(&self, now: u64) -> Option<u64> {
+        if let Some(cron) = &self.cron {
+            return cron
+                .after(&Utc.timestamp_millis(now as i64))
+                .next()
+                .map(|time| time.timestamp_millis() as u64);
+        }
+        return self.interval_minutes.map(|interval| now + interval * MINUTE);
+    }
+}
+
+fn serialize_cron<S>(cron: &Option<Schedule>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    return cron.as_ref().map(|cron| cron.to_string()).serialize(serializer);
+}
+
+fn deserialize_cron<'de, D>(deserializer: D) -> Result<Option<Schedule>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let expression: Option<String> = Deserialize::deserialize(deserializer)?;
+    return expression
+        .map(|expression| Schedule::from_str(&expression).map_err(serde::de::Error::custom))
+        .transpose();
+}
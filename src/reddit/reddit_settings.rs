@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+use crate::{client::settings::SocialNetworkSettings, commons::parsing_tasks::{TaskSeed, ParsingTask}};
+
+use super::{reddit_parsing_task::{RedditListing, RedditParsingTask}, reddit_revisit::RevisitPolicy, reddit_schedule::PollSchedule, reddit_discovery::normalize_thread};
+
+const DEFAULT_COMMUNITY_SNAPSHOT_INTERVAL_HOURS: u64 = 6;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RedditSettings {
+    pub enable_comments_parsing: bool,
+    //subreddit metadata is fetched again after this interval
+    pub community_snapshot_interval_hours: u64,
+    //default revisit policy, seeds may override it
+    pub revisit: RevisitPolicy,
+    //seeds by name
+    pub seeds: HashMap<String, RedditSeed>,
+    //priorities by task type, e.g. "ThreadNew": 10
+    pub priorities: HashMap<String, i64>
+}
+
+impl Default for RedditSettings {
+    fn default() -> Self {
+        Self { 
+            enable_comments_parsing: true, 
+            community_snapshot_interval_hours: DEFAULT_COMMUNITY_SNAPSHOT_INTERVAL_HOURS, 
+            revisit: RevisitPolicy::default(), 
+            seeds: HashMap::new(), 
+            priorities: HashMap::new() 
+        }
+    }
+}
+
+//one entry of "parsing_tasks" in settings file
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct RedditSeed {
+    //subreddit like "r/rust", "r/all" and "r/popular" crawl listings of all subreddits
+    pub thread: Option<String>,
+    pub user: Option<String>,
+    pub search: Option<SearchSettings>,
+    pub listings: Option<Vec<RedditListing>>,
+    pub discovery: Option<DiscoverySettings>,
+    pub revisit: Option<RevisitPolicy>,
+    //without schedule listings are crawled once
+    pub schedule: Option<PollSchedule>,
+    //added to the priority of every task of the seed
+    pub priority: Option<i64>,
+    pub budget: Option<CrawlBudget>
+}
+
+//limits of the seed crawl, applied to subreddits discovered from it too
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CrawlBudget {
+    //pages fetched per listing
+    pub max_pages: Option<u64>,
+    //first visits of posts in the last 24 hours
+    pub max_posts_per_day: Option<u64>,
+    //unix timestamp in seconds, older posts are skipped and 
+    //pagination stops on a page without newer posts
+    pub date_cutoff: Option<u64>,
+    //visits of a post after the first one
+    pub max_revisits: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchSettings {
+    pub query: String,
+    pub thread: Option<String>,
+    pub sort: Option<String>,
+    pub time: Option<String>,
+    pub restrict_sr: Option<bool>
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DiscoverySettings {
+    //number of hops from the seed subreddit
+    pub depth: u64,
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub min_subscribers: u64
+}
+
+impl RedditSettings {
+
+    pub fn new(settings: &SocialNetworkSettings) -> Result<RedditSettings, serde_json::Error> {
+        let mut seeds = HashMap::new();
+        for task in settings.parsing_tasks.iter() {
+            let seed: RedditSeed = serde_json::from_value(Value::Object(task.clone().into_iter().collect()))?;
+            seeds.insert(seed.get_name(), seed);
+        }
+        return Ok(RedditSettings { 
+            enable_comments_parsing: settings.additional_properties
+                .get("enable_comments_parsing")
+                .and_then(|value| value.as_bool())
+                .unwrap_or(true), 
+            community_snapshot_interval_hours: settings.additional_properties
+                .get("community_snapshot_interval_hours")
+                .and_then(|value| value.as_u64())
+                .unwrap_or(DEFAULT_COMMUNITY_SNAPSHOT_INTERVAL_HOURS),
+            revisit: settings.additional_properties
+                .get("revisit")
+                .map(|revisit| serde_json::from_value(revisit.clone()))
+                .transpose()?
+                .unwrap_or_default(),
+            seeds,
+            priorities: settings.additional_properties
+                .get("priorities")
+                .map(|priorities| serde_json::from_value(priorities.clone()))
+                .transpose()?
+                .unwrap_or_default()
+        });
+    }
+
+    pub fn get_seed(&self, task_seed: &Option<TaskSeed>) -> Option<&RedditSeed> {
+        return task_seed.as_ref().and_then(|task_seed| self.seeds.get(&task_seed.name));
+    }
+
+    //policy of the subreddit seed, then of the seed which spawned the task
+    pub fn get_revisit_policy(&self, thread: &str, task_seed: &Option<TaskSeed>) -> &RevisitPolicy {
+        return self.seeds
+            .values()
+            .find(|seed| seed.thread.as_ref().is_some_and(|seed_thread| normalize_thread(seed_thread) == normalize_thread(thread)))
+            .and_then(|seed| seed.revisit.as_ref())
+            .or(self.get_seed(task_seed).and_then(|seed| seed.revisit.as_ref()))
+            .unwrap_or(&self.revisit);
+    }
+
+    pub fn get_priority(&self, task: &RedditParsingTask, task_seed: &Option<TaskSeed>) -> i64 {
+        let task_priority = self.priorities
+            .get(&task.to_string())
+            .copied()
+            .unwrap_or_else(|| task.get_default_priority());
+        let seed_priority = self.get_seed(task_seed).and_then(|seed| seed.priority).unwrap_or(0);
+        return task_priority + seed_priority;
+    }
+
+    pub fn set_priorities(&self, tasks: &mut Vec<ParsingTask>) {
+        for task in tasks.iter_mut() {
+            task.priority = self.get_priority(task.parameters.as_ref_reddit(), &task.seed);
+        }
+    }
+
+    pub fn is_seed_thread(&self, thread: &str) -> bool {
+        return self.seeds
+            .values()
+            .filter_map(|seed| seed.thread.as_ref())
+            .any(|seed_thread| normalize_thread(seed_thread) == normalize_thread(thread));
+    }
+}
+
+impl RedditSeed {
+
+    pub fn get_name(&self) -> String {
+        if let Some(thread) = &self.thread {
+            return thread.clone();
+        }
+        if let Some(user) = &self.user {
+            return format!("u/{}", user.strip_prefix("u/").unwrap_or(user));
+        }
+        return match &self.search {
+            Some(search) => format!("search/{}/{}", search.thread.as_ref().map_or("", |thread| thread), search.query),
+            None => String::from("")
+        };
+    }
+}
\ No newline at end of file